@@ -0,0 +1,284 @@
+use std::{
+    fs::Metadata,
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::io::AsyncWriteExt;
+
+/// Writes `bytes` to `path` without ever leaving a half-written file behind.
+///
+/// The bytes go to a temporary file next to `path` first, which then replaces
+/// it. Where that can't be done, like in read-only directories or on some
+/// network mounts, the file is written in place instead. Failing to write the
+/// bytes themselves, say on a full disk, leaves the original untouched.
+///
+/// Symbolic links are followed, so the file they point to is replaced rather
+/// than the link. Files with other hard links are written in place, since
+/// replacing them would split them off from the other names.
+pub async fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    // A file that doesn't exist yet has no links to follow.
+    let path = tokio::fs::canonicalize(path)
+        .await
+        .unwrap_or_else(|_| path.to_owned());
+    let original = tokio::fs::metadata(&path).await.ok();
+
+    if original.as_ref().is_some_and(has_other_links) {
+        log::debug!(
+            "{} has other hard links, writing it in place",
+            path.display()
+        );
+        return tokio::fs::write(&path, bytes).await;
+    }
+
+    match replace(&path, bytes, original.as_ref()).await {
+        Ok(()) => {
+            log::debug!("saved {} through a temporary file", path.display());
+            Ok(())
+        }
+        Err(Failure::Write(error)) => Err(error),
+        Err(Failure::Replace(error)) => {
+            log::debug!(
+                "replacing {} failed ({error}), writing it in place",
+                path.display()
+            );
+
+            tokio::fs::write(&path, bytes).await
+        }
+    }
+}
+
+/// Why replacing a file through a temporary one failed.
+enum Failure {
+    /// The temporary file couldn't be made or moved into place, which
+    /// writing in place may get around.
+    Replace(io::Error),
+    /// The bytes couldn't be written, which would fail in place too, only
+    /// after cutting the original short.
+    Write(io::Error),
+}
+
+async fn replace(path: &Path, bytes: &[u8], original: Option<&Metadata>) -> Result<(), Failure> {
+    let temporary = temporary_path(path).map_err(Failure::Replace)?;
+
+    // Made only by us, so a file or link already in its place is never
+    // written through or removed.
+    let mut file = temporary_options(original)
+        .open(&temporary)
+        .await
+        .map_err(Failure::Replace)?;
+
+    let result = async {
+        // The permissions and the owner are set before any of the text is
+        // written, so it is never readable by more users than the original.
+        if let Some(original) = original {
+            tokio::fs::set_permissions(&temporary, original.permissions())
+                .await
+                .map_err(Failure::Replace)?;
+            preserve_owner(&temporary, original);
+        }
+
+        file.write_all(bytes).await.map_err(Failure::Write)?;
+        file.sync_all().await.map_err(Failure::Write)?;
+        drop(file);
+
+        tokio::fs::rename(&temporary, path)
+            .await
+            .map_err(Failure::Replace)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temporary).await;
+    }
+
+    result
+}
+
+/// A hidden file next to `path`, so renaming it over `path` never has to
+/// cross file systems.
+///
+/// Every write gets a name of its own, so writes of the same file running at
+/// the same time don't clash.
+fn temporary_path(path: &Path) -> io::Result<PathBuf> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+    Ok(path.with_file_name(format!(
+        ".{}.{}-{}.rio-tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+/// Creates a new file that nobody but the original's readers can read.
+#[cfg(unix)]
+fn temporary_options(original: Option<&Metadata>) -> tokio::fs::OpenOptions {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options
+        .write(true)
+        .create_new(true)
+        .mode(original.map_or(0o666, |original| original.permissions().mode() & 0o777));
+    options
+}
+
+#[cfg(not(unix))]
+fn temporary_options(_original: Option<&Metadata>) -> tokio::fs::OpenOptions {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+/// Gives `path` the owner and group of the file it replaces.
+///
+/// Only root can hand files to other users, so this is allowed to fail and
+/// the new file then keeps belonging to us.
+#[cfg(unix)]
+fn preserve_owner(path: &Path, original: &Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    let _ = std::os::unix::fs::chown(path, Some(original.uid()), Some(original.gid()));
+}
+
+#[cfg(not(unix))]
+fn preserve_owner(_path: &Path, _original: &Metadata) {}
+
+#[cfg(unix)]
+fn has_other_links(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        fs,
+        os::unix::fs::{symlink, MetadataExt, PermissionsExt},
+        path::PathBuf,
+    };
+
+    use super::write;
+
+    /// An empty folder for one test, removed again when it is dropped.
+    struct Folder(PathBuf);
+
+    impl Folder {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("rio-editor-atomic-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Folder(path)
+        }
+    }
+
+    impl Drop for Folder {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_permissions() {
+        let folder = Folder::new("permissions");
+        let path = folder.0.join("notes.txt");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write(&path, b"new").await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o640);
+    }
+
+    #[tokio::test]
+    async fn creates_new_files() {
+        let folder = Folder::new("new");
+        let path = folder.0.join("notes.txt");
+
+        write(&path, b"new").await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn writes_through_symlinks() {
+        let folder = Folder::new("symlink");
+        let target = folder.0.join("target.txt");
+        let link = folder.0.join("link.txt");
+        fs::write(&target, "old").unwrap();
+        symlink(&target, &link).unwrap();
+
+        write(&link, b"new").await.unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn keeps_hard_links() {
+        let folder = Folder::new("hard-link");
+        let path = folder.0.join("notes.txt");
+        let other = folder.0.join("other.txt");
+        fs::write(&path, "old").unwrap();
+        fs::hard_link(&path, &other).unwrap();
+
+        write(&path, b"new").await.unwrap();
+
+        assert_eq!(fs::read_to_string(&other).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn keeps_private_files_private() {
+        let folder = Folder::new("private");
+        let path = folder.0.join("notes.txt");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write(&path, b"new").await.unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn writes_the_same_file_twice_at_once() {
+        let folder = Folder::new("concurrent");
+        let path = folder.0.join("notes.txt");
+        fs::write(&path, "old").unwrap();
+
+        let (first, second) = tokio::join!(write(&path, b"first"), write(&path, b"second"));
+        first.unwrap();
+        second.unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text == "first" || text == "second");
+        assert_eq!(fs::read_dir(&folder.0).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn leaves_no_temporary_file() {
+        let folder = Folder::new("temporary");
+        let path = folder.0.join("notes.txt");
+        fs::write(&path, "old").unwrap();
+
+        write(&path, b"new").await.unwrap();
+
+        assert_eq!(fs::read_dir(&folder.0).unwrap().count(), 1);
+    }
+}
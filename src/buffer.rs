@@ -0,0 +1,1415 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use iced::widget::text_editor;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::cursor;
+use crate::encoding::TextEncoding;
+use crate::history::{self, History, Snapshot, Stroke};
+use crate::indent::{self, Indent};
+use crate::language;
+use crate::line_ending::LineEnding;
+use crate::OpenedFile;
+
+pub struct Buffer {
+    pub content: text_editor::Content,
+    pub path: Option<PathBuf>,
+    pub is_dirty: bool,
+    /// Whether edits are ignored, leaving only moving and selecting.
+    pub read_only: bool,
+    /// Whether the file can be written to, as far as was known when it was
+    /// last read or written.
+    pub is_writable: bool,
+    /// The extension picked by the user to highlight this buffer with,
+    /// overriding the one of its path.
+    pub language: Option<String>,
+    /// The indentation picked for this buffer, overriding the default one.
+    pub indent: Option<Indent>,
+    /// Whether the file mixed tabs and spaces for indentation when it was
+    /// read, until that is normalized.
+    pub mixed_indent: bool,
+    /// The line ending restored when the buffer is saved.
+    pub line_ending: LineEnding,
+    /// Whether the file mixed LF and CRLF line breaks when it was read,
+    /// which saving turns all into `line_ending`.
+    pub mixed_line_endings: bool,
+    /// The encoding the buffer is saved in.
+    pub encoding: TextEncoding,
+    /// The file on disk as it was last read or written.
+    pub stamp: Option<FileStamp>,
+    /// Whether another program changed the file since it was read or
+    /// written, and saving would overwrite those changes.
+    pub changed_on_disk: bool,
+    /// Whether the user chose to keep editing despite the change on disk.
+    pub keep_changes: bool,
+    /// How many lines the text has, kept up to date on every edit.
+    pub line_count: usize,
+    pub stats: Stats,
+    /// Whether the text changed since `stats` were last counted.
+    pub stats_outdated: bool,
+    saved: Option<Fingerprint>,
+    /// Where the selection was started, as the editor doesn't tell.
+    anchor: Option<(usize, usize)>,
+    history: History,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Buffer {
+            content: text_editor::Content::new(),
+            path: None,
+            is_dirty: true,
+            read_only: false,
+            is_writable: true,
+            language: None,
+            indent: None,
+            mixed_indent: false,
+            line_ending: LineEnding::default(),
+            mixed_line_endings: false,
+            encoding: TextEncoding::default(),
+            stamp: None,
+            changed_on_disk: false,
+            keep_changes: false,
+            line_count: 1,
+            stats: Stats::default(),
+            stats_outdated: false,
+            saved: None,
+            anchor: None,
+            history: History::new(history::DEFAULT_LIMIT, history::DEFAULT_BYTE_LIMIT),
+        }
+    }
+
+    /// An untitled buffer starting with `text`, highlighted as `extension`.
+    pub fn from_template(text: &str, extension: &str) -> Self {
+        let content = text_editor::Content::with(text);
+
+        Buffer {
+            line_count: content.line_count().max(1),
+            stats: Stats::count(text),
+            language: Some(extension.to_owned()),
+            content,
+            ..Buffer::new()
+        }
+    }
+
+    pub fn open(file: &OpenedFile) -> Self {
+        let mut buffer = Buffer {
+            content: text_editor::Content::with(&file.text.replace("\r\n", "\n")),
+            path: Some(file.path.clone()),
+            is_dirty: false,
+            // Files that can't be saved start out read-only, so nothing is
+            // typed that would have to be saved elsewhere.
+            read_only: !file.is_writable,
+            is_writable: file.is_writable,
+            language: None,
+            indent: None,
+            mixed_indent: false,
+            line_ending: LineEnding::detect(&file.text),
+            mixed_line_endings: LineEnding::is_mixed(&file.text),
+            encoding: file.encoding,
+            stamp: file.stamp,
+            changed_on_disk: false,
+            keep_changes: false,
+            line_count: 1,
+            stats: Stats::default(),
+            stats_outdated: false,
+            saved: None,
+            anchor: None,
+            history: History::new(history::DEFAULT_LIMIT, history::DEFAULT_BYTE_LIMIT),
+        };
+
+        let text = raw_text(&buffer.content);
+        buffer.saved = Some(buffer.fingerprint(&text));
+        buffer.mixed_indent = indent::is_mixed(&text);
+        buffer.line_count = buffer.content.line_count().max(1);
+        buffer.stats = Stats::count(&text);
+        buffer
+    }
+
+    /// Replaces the text with the one just read from disk, keeping the
+    /// cursor, the language and indentation picked for the buffer and
+    /// whether it is read-only.
+    pub fn reload(&mut self, file: &OpenedFile) {
+        let (line, column) = self.content.cursor_position();
+
+        *self = Buffer {
+            language: self.language.take(),
+            indent: self.indent,
+            read_only: self.read_only,
+            ..Buffer::open(file)
+        };
+
+        cursor::move_to(&mut self.content, line, column);
+    }
+
+    pub fn name(&self) -> &str {
+        self.path
+            .as_deref()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str())
+            .unwrap_or("New File")
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        self.path.as_deref()?.extension()?.to_str()
+    }
+
+    /// The extension used to pick the syntax of this buffer.
+    pub fn syntax(&self) -> Option<&str> {
+        self.language.as_deref().or_else(|| self.extension())
+    }
+
+    /// Whether this is an untitled buffer nobody has typed into yet, which
+    /// can be replaced by an opened file without losing anything.
+    pub fn is_pristine(&self) -> bool {
+        self.path.is_none() && self.content.text().trim().is_empty()
+    }
+
+    /// The spaces that take the cursor to the next tab stop.
+    pub fn soft_tab(&self, tab_width: usize) -> String {
+        let (line, column) = self.content.cursor_position();
+        let before = self
+            .content
+            .line(line)
+            .map_or(0, |text| cursor::characters_before(&text, column));
+
+        " ".repeat(tab_width - before % tab_width)
+    }
+
+    /// The text Enter inserts to start a line with the indentation of the
+    /// cursor line, one level deeper if the text before the cursor opens a
+    /// block.
+    pub fn indented_newline(&self, indent: Indent) -> String {
+        let (line, column) = self.content.cursor_position();
+        let before = self
+            .content
+            .line(line)
+            .map(|text| {
+                let end = cursor::column_of(&text, cursor::characters_before(&text, column));
+                String::from(&text[..end])
+            })
+            .unwrap_or_default();
+
+        let leading = &before[..before.len() - before.trim_start_matches([' ', '\t']).len()];
+        let mut newline = format!("\n{leading}");
+
+        if language::opens_block(self.syntax(), &before) {
+            newline.push_str(&indent.level());
+        }
+
+        newline
+    }
+
+    /// Adds one level of indentation to every line the selection touches, as
+    /// a single undoable step.
+    pub fn indent_lines(&mut self, indent: Indent) {
+        let level = indent.level();
+
+        self.change_lines(|line| (format!("{level}{line}"), 0, level.len() as isize));
+    }
+
+    /// Inserts `prefix` at the start of every line the selection touches, as
+    /// a single undoable step.
+    ///
+    /// The text editor knows a single cursor and a single continuous
+    /// selection, so this stands in for typing on many lines at once.
+    pub fn prefix_lines(&mut self, prefix: &str) {
+        if prefix.is_empty() {
+            return;
+        }
+
+        self.change_lines(|line| (format!("{prefix}{line}"), 0, prefix.len() as isize));
+    }
+
+    /// Removes one level of indentation from the cursor line, or from every
+    /// line the selection touches, as a single undoable step.
+    pub fn dedent(&mut self, indent: Indent) {
+        self.change_lines(|line| {
+            let removed = indent.leading_level(line);
+
+            (line[removed..].to_owned(), 0, -(removed as isize))
+        });
+    }
+
+    /// Copies the selection right after itself and selects the copy, or the
+    /// cursor line below itself if nothing is selected, as a single undoable
+    /// step.
+    pub fn duplicate(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        let text = raw_text(&self.content);
+        let head = self.content.cursor_position();
+
+        match self.selection_anchor() {
+            Some(anchor) => {
+                let start = cursor::offset(&text, anchor.min(head));
+                let end = cursor::offset(&text, anchor.max(head));
+                let selection = &text[start..end];
+
+                let duplicated = format!("{}{selection}{}", &text[..end], &text[end..]);
+                let copy_start = cursor::position(&duplicated, end);
+                let copy_end = cursor::position(&duplicated, end + selection.len());
+
+                self.set_text(&duplicated, copy_end);
+                self.select_range(copy_start, copy_end);
+            }
+            None => {
+                let mut lines: Vec<&str> = text.split('\n').collect();
+                let line = head.0.min(lines.len() - 1);
+
+                lines.insert(line + 1, lines[line]);
+                self.set_text(&lines.join("\n"), (line + 1, head.1));
+            }
+        }
+    }
+
+    /// Removes the lines the selection or the cursor touches, along with their
+    /// line breaks, as a single undoable step.
+    ///
+    /// The cursor stays in its column on the line that followed them.
+    pub fn delete_lines(&mut self) {
+        let text = raw_text(&self.content);
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        let (_, touched) = self.selected_lines();
+        let first = (*touched.start()).min(lines.len() - 1);
+        let last = (*touched.end()).min(lines.len() - 1);
+        let (_, column) = self.content.cursor_position();
+
+        lines.drain(first..=last);
+
+        self.set_text(&lines.join("\n"), (first, column));
+    }
+
+    /// Joins the lines the selection touches into one, or the cursor line
+    /// with the one below it, as a single undoable step.
+    ///
+    /// The whitespace around every joint turns into a single space, and the
+    /// cursor is left at the last joint.
+    pub fn join_lines(&mut self) {
+        let text = raw_text(&self.content);
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        let (_, touched) = self.selected_lines();
+        let first = *touched.start();
+        let last = (*touched.end()).max(first + 1);
+
+        if last >= lines.len() {
+            return;
+        }
+
+        let mut joined = lines[first].trim_end_matches([' ', '\t']).to_owned();
+        let mut joint = joined.len();
+
+        for line in &lines[first + 1..=last] {
+            let line = line.trim_matches([' ', '\t']);
+
+            joint = joined.len();
+
+            if !line.is_empty() {
+                if !joined.is_empty() {
+                    joined.push(' ');
+                }
+
+                joined.push_str(line);
+            }
+        }
+
+        lines.drain(first + 1..=last);
+        lines[first] = &joined;
+
+        self.set_text(&lines.join("\n"), (first, joint));
+    }
+
+    /// Changes the case of the selection, or of the word at the cursor if
+    /// nothing is selected, and selects the result so changes can be chained.
+    /// This is a single undoable step.
+    pub fn change_case(&mut self, case: Case) {
+        if self.read_only {
+            return;
+        }
+
+        let text = raw_text(&self.content);
+        let head = cursor::offset(&text, self.content.cursor_position());
+        let (anchor, head) = match self.selection_anchor() {
+            Some(anchor) => (cursor::offset(&text, anchor), head),
+            None => match word_at(&text, head) {
+                Some(word) => (word.start, word.end),
+                None => return,
+            },
+        };
+
+        let (start, end) = (anchor.min(head), anchor.max(head));
+        let changed = case.apply(&text[start..end]);
+        let result = format!("{}{changed}{}", &text[..start], &text[end..]);
+
+        let (anchor, head) = if anchor <= head {
+            (start, start + changed.len())
+        } else {
+            (start + changed.len(), start)
+        };
+        let (anchor, head) = (
+            cursor::position(&result, anchor),
+            cursor::position(&result, head),
+        );
+
+        self.set_text(&result, head);
+        self.select_range(anchor, head);
+    }
+
+    /// Sorts the lines the selection touches, keeping lines that compare equal
+    /// in their order, and selects them whole. This is a single undoable
+    /// step.
+    pub fn sort_lines(&mut self, sort: Sort) {
+        let text = raw_text(&self.content);
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        let (_, touched) = self.selected_lines();
+        let first = *touched.start();
+        let last = (*touched.end()).min(lines.len() - 1);
+
+        if first >= last {
+            return;
+        }
+
+        lines[first..=last].sort_by(|a, b| {
+            let order = if sort.ignore_case {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            } else {
+                a.cmp(b)
+            };
+
+            if sort.reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        let end = (last, lines[last].len());
+
+        self.set_text(&lines.join("\n"), end);
+        self.select_range((first, 0), end);
+    }
+
+    /// Swaps the lines the selection touches with the line above them, or
+    /// below them if `down`, as a single undoable step.
+    ///
+    /// The cursor and the selection move along with the lines, and nothing
+    /// happens once they reach the first or the last line. The empty line
+    /// after a final newline isn't one that moves, so the newline stays last.
+    pub fn move_lines(&mut self, down: bool) {
+        if self.read_only {
+            return;
+        }
+
+        let text = raw_text(&self.content);
+        let mut lines: Vec<&str> = text.split('\n').collect();
+        let movable = lines.len() - usize::from(text.ends_with('\n'));
+        let (anchor, touched) = self.selected_lines();
+
+        if *touched.start() >= movable {
+            return;
+        }
+
+        let (first, last) = (*touched.start(), (*touched.end()).min(movable - 1));
+
+        if down {
+            if last + 1 >= movable {
+                return;
+            }
+
+            let below = lines.remove(last + 1);
+            lines.insert(first, below);
+        } else {
+            if first == 0 {
+                return;
+            }
+
+            let above = lines.remove(first - 1);
+            lines.insert(last, above);
+        }
+
+        let moved = |(line, column): (usize, usize)| {
+            if down {
+                (line + 1, column)
+            } else {
+                (line.saturating_sub(1), column)
+            }
+        };
+        let head = moved(self.content.cursor_position());
+
+        self.set_text(&lines.join("\n"), head);
+
+        if let Some(anchor) = anchor {
+            self.select_range(moved(anchor), head);
+        }
+    }
+
+    /// Comments out the lines the selection touches with `marker`, or
+    /// uncomments them if they all are already, as a single undoable step.
+    ///
+    /// The markers line up at the smallest indentation of those lines, and
+    /// blank lines are left alone.
+    pub fn toggle_comment(&mut self, marker: &str) {
+        let lines: Vec<String> = self
+            .selected_lines()
+            .1
+            .filter_map(|index| self.content.line(index).map(|line| String::from(&*line)))
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+
+        let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+
+        if lines
+            .iter()
+            .all(|line| line.trim_start().starts_with(marker))
+        {
+            self.change_lines(|line| {
+                let at = indentation(line);
+                let Some(rest) = line[at..].strip_prefix(marker) else {
+                    return (line.to_owned(), 0, 0);
+                };
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                let removed = line.len() - at - rest.len();
+
+                (format!("{}{rest}", &line[..at]), at, -(removed as isize))
+            });
+        } else {
+            let at = lines
+                .iter()
+                .map(|line| indentation(line))
+                .min()
+                .unwrap_or_default();
+
+            self.change_lines(|line| {
+                if line.trim().is_empty() {
+                    return (line.to_owned(), 0, 0);
+                }
+
+                let (indentation, rest) = line.split_at(at);
+
+                (
+                    format!("{indentation}{marker} {rest}"),
+                    at,
+                    marker.len() as isize + 1,
+                )
+            });
+        }
+    }
+
+    /// The other end of the selection, if there is one, and the lines the
+    /// selection or the cursor touches.
+    fn selected_lines(&self) -> (Option<(usize, usize)>, RangeInclusive<usize>) {
+        let head = self.content.cursor_position();
+        let anchor = self.selection_anchor();
+        let (start, end) = match anchor {
+            Some(anchor) => (anchor.min(head), anchor.max(head)),
+            None => (head, head),
+        };
+
+        // A selection that ends right at the start of a line doesn't touch
+        // that line.
+        let last = if end.0 > start.0 && end.1 == 0 {
+            end.0 - 1
+        } else {
+            end.0
+        };
+
+        (anchor, start.0..=last)
+    }
+
+    /// Rewrites the lines the selection touches with `change`, which returns
+    /// the new line, the column from which the rest of it moved and how many
+    /// bytes it moved by.
+    ///
+    /// The cursor and the selection move along with the text, so the same
+    /// lines stay selected.
+    fn change_lines(&mut self, change: impl Fn(&str) -> (String, usize, isize)) {
+        if self.read_only {
+            return;
+        }
+
+        let head = self.content.cursor_position();
+        let (anchor, touched) = self.selected_lines();
+
+        let mut lines: Vec<String> = raw_text(&self.content)
+            .split('\n')
+            .map(str::to_owned)
+            .collect();
+        let mut shifts = vec![(0, 0); lines.len()];
+
+        for (line, shift) in lines
+            .iter_mut()
+            .zip(&mut shifts)
+            .take(touched.end() + 1)
+            .skip(*touched.start())
+        {
+            let (changed, at, by) = change(line);
+
+            *line = changed;
+            *shift = (at, by);
+        }
+
+        if shifts.iter().all(|(_, by)| *by == 0) {
+            return;
+        }
+
+        // Positions inside removed text end up where it was.
+        let shifted = |(line, column): (usize, usize)| match shifts.get(line) {
+            Some(&(at, by)) if column >= at => (line, column.saturating_add_signed(by).max(at)),
+            _ => (line, column),
+        };
+
+        self.set_text(&lines.join("\n"), shifted(head));
+
+        if let Some(anchor) = anchor {
+            self.select_range(shifted(anchor), shifted(head));
+        }
+    }
+
+    pub fn edit(&mut self, action: text_editor::Action) {
+        let is_edit = action.is_edit();
+
+        if is_edit && self.read_only {
+            return;
+        }
+
+        match &action {
+            text_editor::Action::Edit(edit) => {
+                // Replacing a selection is a step of its own.
+                let stroke = match edit {
+                    _ if self.content.selection().is_some() => None,
+                    text_editor::Edit::Insert(c) if c.is_whitespace() => Some(Stroke::Space),
+                    text_editor::Edit::Insert(_) => Some(Stroke::Word),
+                    text_editor::Edit::Enter => Some(Stroke::Space),
+                    text_editor::Edit::Backspace | text_editor::Edit::Delete => {
+                        Some(Stroke::Delete)
+                    }
+                    _ => None,
+                };
+
+                self.history.record(stroke, || snapshot(&self.content));
+            }
+            _ => self.history.seal(),
+        }
+
+        let cursor = self.content.cursor_position();
+        let had_selection = self.content.selection().is_some();
+
+        match &action {
+            // Extending a selection keeps where it was started.
+            text_editor::Action::Select(_) if had_selection => {}
+            text_editor::Action::Select(_) => self.anchor = Some(cursor),
+            text_editor::Action::Move(_)
+            | text_editor::Action::SelectWord
+            | text_editor::Action::SelectLine
+            | text_editor::Action::Edit(_) => self.anchor = None,
+            _ => {}
+        }
+
+        let is_click = matches!(action, text_editor::Action::Click(_));
+        self.content.edit(action);
+
+        // Dragging after a click selects from where the click landed.
+        if is_click {
+            self.anchor = Some(self.content.cursor_position());
+        }
+
+        if is_edit {
+            self.text_changed();
+        }
+    }
+
+    /// Selects the text between byte columns `start` and `end` of `line`,
+    /// leaving the cursor at the end of the selection.
+    pub fn select(&mut self, line: usize, start: usize, end: usize) {
+        cursor::select(&mut self.content, line, start, end);
+        self.anchor = Some((line, start));
+    }
+
+    /// Selects from `anchor` to `head`, leaving the cursor at `head`.
+    fn select_range(&mut self, anchor: (usize, usize), head: (usize, usize)) {
+        cursor::select_range(&mut self.content, anchor, head);
+        self.anchor = Some(anchor);
+    }
+
+    /// The other end of the selection, if there is one.
+    ///
+    /// The anchor recorded when the selection was started is trusted only
+    /// while the text between it and the cursor is still what is selected,
+    /// otherwise it is guessed from the selected text.
+    fn selection_anchor(&self) -> Option<(usize, usize)> {
+        let selection = self.content.selection()?;
+
+        if let Some(anchor) = self.anchor {
+            let text = raw_text(&self.content);
+            let head = self.content.cursor_position();
+            let start = cursor::offset(&text, anchor.min(head));
+            let end = cursor::offset(&text, anchor.max(head));
+
+            if text.get(start..end) == Some(selection.as_str()) {
+                return Some(anchor);
+            }
+        }
+
+        cursor::selection_anchor(&self.content)
+    }
+
+    /// Replaces the whole text as a single undoable step and puts the cursor
+    /// at `cursor`.
+    pub fn set_text(&mut self, text: &str, cursor: (usize, usize)) {
+        if self.read_only {
+            return;
+        }
+
+        self.history.record(None, || snapshot(&self.content));
+        self.history.seal();
+
+        self.restore(Snapshot {
+            text: text.to_owned(),
+            cursor,
+        });
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.read_only {
+            return;
+        }
+
+        self.line_ending = line_ending;
+        self.mixed_line_endings = false;
+        self.text_changed();
+    }
+
+    pub fn set_encoding(&mut self, encoding: TextEncoding) {
+        if self.read_only {
+            return;
+        }
+
+        self.encoding = encoding;
+        self.text_changed();
+    }
+
+    /// Strips spaces and tabs from the end of every line, and blank lines
+    /// from the end of the text down to a single line break, as a single
+    /// undoable step. The cursor is pulled back if it was in what got
+    /// stripped.
+    pub fn trim_trailing_whitespace(&mut self) {
+        let text = raw_text(&self.content);
+        let mut trimmed = text
+            .split('\n')
+            .map(|line| line.trim_end_matches([' ', '\t']))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if trimmed.ends_with("\n\n") {
+            trimmed.truncate(trimmed.trim_end_matches('\n').len());
+            trimmed.push('\n');
+        }
+
+        if trimmed == text {
+            return;
+        }
+
+        let (line, column) = self.content.cursor_position();
+        let column = trimmed
+            .split('\n')
+            .nth(line)
+            .map_or(0, |trimmed| column.min(trimmed.len()));
+
+        self.set_text(&trimmed, (line, column));
+    }
+
+    /// Rewrites the leading whitespace of every line in `indent` as a single
+    /// undoable step, keeping the cursor on the same character.
+    pub fn normalize_indent(&mut self, indent: Indent) {
+        if self.read_only {
+            return;
+        }
+
+        self.mixed_indent = false;
+
+        let text = raw_text(&self.content);
+        let normalized = text
+            .split('\n')
+            .map(|line| indent.normalize(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if normalized == text {
+            return;
+        }
+
+        let (line, column) = self.content.cursor_position();
+        let column = match (text.split('\n').nth(line), normalized.split('\n').nth(line)) {
+            (Some(before), Some(after)) => (column + after.len()).saturating_sub(before.len()),
+            _ => 0,
+        };
+
+        self.set_text(&normalized, (line, column));
+    }
+
+    /// Ends the text with a line break as a single undoable step, unless it
+    /// is empty or already ends with one.
+    pub fn insert_final_newline(&mut self) {
+        let text = raw_text(&self.content);
+
+        if text.is_empty() || text.ends_with('\n') {
+            return;
+        }
+
+        let cursor = self.content.cursor_position();
+        self.set_text(&format!("{text}\n"), cursor);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.read_only && self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.read_only && self.history.can_redo()
+    }
+
+    pub fn undo(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        if let Some(snapshot) = self.history.undo(snapshot(&self.content)) {
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.read_only {
+            return;
+        }
+
+        if let Some(snapshot) = self.history.redo(snapshot(&self.content)) {
+            self.restore(snapshot);
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        let (line, column) = snapshot.cursor;
+
+        self.content = text_editor::Content::with(&snapshot.text);
+        self.anchor = None;
+        cursor::move_to(&mut self.content, line, column);
+        self.text_changed();
+    }
+
+    fn text_changed(&mut self) {
+        // Most edits change the length, which is told without copying the
+        // whole text to hash it on every keystroke.
+        self.is_dirty = match self.saved {
+            Some(saved) if saved.len == text_len(&self.content) => {
+                saved != self.fingerprint(&raw_text(&self.content))
+            }
+            _ => true,
+        };
+        self.line_count = self.content.line_count().max(1);
+        self.stats_outdated = true;
+    }
+
+    /// Counts the text again if it changed since it was last counted.
+    pub fn refresh_stats(&mut self) {
+        if self.stats_outdated {
+            self.stats = Stats::count(&raw_text(&self.content));
+            self.stats_outdated = false;
+        }
+    }
+
+    /// Hashes `text` along with how it is written to disk, so changing either
+    /// one makes the buffer dirty.
+    pub fn fingerprint(&self, text: &str) -> Fingerprint {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        self.line_ending.hash(&mut hasher);
+        self.mixed_line_endings.hash(&mut hasher);
+        self.encoding.hash(&mut hasher);
+
+        Fingerprint {
+            len: text.len(),
+            hash: hasher.finish(),
+        }
+    }
+
+    /// Records that the text with `fingerprint` was written to `path`.
+    /// Anything typed since the write started keeps the buffer dirty.
+    ///
+    /// A buffer that was read-only only because its file couldn't be written
+    /// can be edited again once it is saved somewhere that can.
+    pub fn mark_saved(
+        &mut self,
+        path: PathBuf,
+        stamp: Option<FileStamp>,
+        fingerprint: Fingerprint,
+    ) {
+        self.path = Some(path);
+        self.stamp = stamp;
+
+        if !self.is_writable {
+            self.read_only = false;
+        }
+
+        self.is_writable = true;
+        self.changed_on_disk = false;
+        self.keep_changes = false;
+        self.saved = Some(fingerprint);
+        self.is_dirty = fingerprint != self.fingerprint(&raw_text(&self.content));
+    }
+}
+
+/// The text of a buffer as it was saved, told apart from others by its
+/// length first and its hash second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    len: usize,
+    hash: u64,
+}
+
+fn snapshot(content: &text_editor::Content) -> Snapshot {
+    Snapshot {
+        text: raw_text(content),
+        cursor: content.cursor_position(),
+    }
+}
+
+/// The length in bytes of `raw_text(content)`, without copying it.
+fn text_len(content: &text_editor::Content) -> usize {
+    content.lines().map(|line| line.len()).sum::<usize>() + content.line_count().saturating_sub(1)
+}
+
+/// The exact lines of `content` joined by newlines.
+///
+/// Unlike `Content::text`, this never appends a trailing newline, so feeding
+/// it back into `Content::with` yields the same lines.
+pub fn raw_text(content: &text_editor::Content) -> String {
+    content
+        .lines()
+        .enumerate()
+        .fold(String::new(), |mut text, (index, line)| {
+            if index > 0 {
+                text.push('\n');
+            }
+
+            text.push_str(&line);
+            text
+        })
+}
+
+/// The byte range of the word at byte `offset` of `text`, or ending there.
+fn word_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    text.split_word_bound_indices()
+        .map(|(start, word)| start..start + word.len())
+        .filter(|range| range.start <= offset && offset <= range.end)
+        .find(|range| text[range.clone()].chars().any(char::is_alphanumeric))
+}
+
+/// The case `Buffer::change_case` turns text into.
+#[derive(Debug, Clone, Copy)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// Every word capitalized and the rest of it in lowercase.
+    Title,
+}
+
+impl Case {
+    fn apply(self, text: &str) -> String {
+        match self {
+            Case::Upper => text.to_uppercase(),
+            Case::Lower => text.to_lowercase(),
+            Case::Title => text
+                .split_word_bounds()
+                .flat_map(|word| {
+                    let mut chars = word.chars();
+                    let first = chars.next().into_iter().flat_map(char::to_uppercase);
+
+                    first.chain(chars.flat_map(char::to_lowercase))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How `Buffer::sort_lines` orders lines.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sort {
+    pub reverse: bool,
+    pub ignore_case: bool,
+}
+
+/// The modification time and size of a file, to tell when another program
+/// changes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+}
+
+impl FileStamp {
+    pub fn new(metadata: &fs::Metadata) -> Self {
+        FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+}
+
+impl Stats {
+    /// Counts `text`, using Unicode word boundaries and counting every
+    /// grapheme cluster as a single character.
+    pub fn count(text: &str) -> Self {
+        Stats {
+            lines: text.split('\n').count(),
+            words: text.unicode_words().count(),
+            chars: text.graphemes(true).count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::widget::text_editor::{Action, Content, Edit};
+
+    use super::{raw_text, Buffer, Case, Sort};
+    use crate::cursor;
+
+    fn buffer(text: &str, cursor: (usize, usize)) -> Buffer {
+        let mut buffer = Buffer {
+            content: Content::with(text),
+            ..Buffer::new()
+        };
+
+        cursor::move_to(&mut buffer.content, cursor.0, cursor.1);
+        buffer
+    }
+
+    fn saved(text: &str, cursor: (usize, usize)) -> Buffer {
+        let mut buffer = buffer(text, cursor);
+
+        buffer.saved = Some(buffer.fingerprint(text));
+        buffer.is_dirty = false;
+        buffer
+    }
+
+    #[test]
+    fn is_clean_again_after_deleting_what_was_typed() {
+        let mut buffer = saved("one", (0, 3));
+
+        buffer.edit(Action::Edit(Edit::Insert('s')));
+        assert!(buffer.is_dirty);
+
+        buffer.edit(Action::Edit(Edit::Backspace));
+        assert!(!buffer.is_dirty);
+    }
+
+    #[test]
+    fn is_dirty_after_replacing_text_of_the_same_length() {
+        let mut buffer = saved("one", (0, 3));
+
+        buffer.edit(Action::Edit(Edit::Backspace));
+        buffer.edit(Action::Edit(Edit::Insert('x')));
+
+        assert!(buffer.is_dirty);
+    }
+
+    #[test]
+    fn undoes_a_run_of_deletions_at_once() {
+        let mut buffer = saved("one two", (0, 7));
+
+        buffer.edit(Action::Edit(Edit::Backspace));
+        buffer.edit(Action::Edit(Edit::Backspace));
+        buffer.edit(Action::Edit(Edit::Backspace));
+        buffer.undo();
+
+        assert_eq!(raw_text(&buffer.content), "one two");
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn moves_a_line_up() {
+        let mut buffer = buffer("one\ntwo\nthree", (1, 2));
+
+        buffer.move_lines(false);
+
+        assert_eq!(raw_text(&buffer.content), "two\none\nthree");
+        assert_eq!(buffer.content.cursor_position(), (0, 2));
+    }
+
+    #[test]
+    fn moves_a_line_down() {
+        let mut buffer = buffer("one\ntwo\nthree", (1, 2));
+
+        buffer.move_lines(true);
+
+        assert_eq!(raw_text(&buffer.content), "one\nthree\ntwo");
+        assert_eq!(buffer.content.cursor_position(), (2, 2));
+    }
+
+    #[test]
+    fn keeps_the_first_line_at_the_top() {
+        let mut buffer = buffer("one\ntwo", (0, 0));
+
+        buffer.move_lines(false);
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo");
+        assert_eq!(buffer.content.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn keeps_the_last_line_at_the_bottom() {
+        let mut buffer = buffer("one\ntwo", (1, 0));
+
+        buffer.move_lines(true);
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo");
+        assert_eq!(buffer.content.cursor_position(), (1, 0));
+    }
+
+    #[test]
+    fn keeps_the_last_line_above_the_final_newline() {
+        let mut buffer = buffer("one\ntwo\n", (1, 0));
+
+        buffer.move_lines(true);
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo\n");
+        assert_eq!(buffer.content.cursor_position(), (1, 0));
+    }
+
+    #[test]
+    fn moves_the_last_line_up_above_the_final_newline() {
+        let mut buffer = buffer("one\ntwo\n", (1, 0));
+
+        buffer.move_lines(false);
+
+        assert_eq!(raw_text(&buffer.content), "two\none\n");
+        assert_eq!(buffer.content.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn leaves_the_line_after_the_final_newline_alone() {
+        let mut buffer = buffer("one\ntwo\n", (2, 0));
+
+        buffer.move_lines(false);
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo\n");
+        assert_eq!(buffer.content.cursor_position(), (2, 0));
+    }
+
+    #[test]
+    fn moves_selected_lines_together() {
+        let mut buffer = buffer("one\ntwo\nthree\nfour\n", (0, 0));
+        buffer.select_range((1, 0), (2, 5));
+
+        buffer.move_lines(true);
+
+        assert_eq!(raw_text(&buffer.content), "one\nfour\ntwo\nthree\n");
+        assert_eq!(buffer.content.cursor_position(), (3, 5));
+        assert_eq!(buffer.content.selection().as_deref(), Some("two\nthree"));
+    }
+
+    #[test]
+    fn comments_out_lines_at_their_smallest_indentation() {
+        let mut buffer = buffer("  one\n\n    two", (0, 0));
+        buffer.select_range((0, 0), (2, 7));
+
+        buffer.toggle_comment("//");
+
+        assert_eq!(raw_text(&buffer.content), "  // one\n\n  //   two");
+        assert_eq!(buffer.content.cursor_position(), (2, 10));
+    }
+
+    #[test]
+    fn uncomments_the_cursor_line() {
+        let mut buffer = buffer("  // one\n  // two", (0, 5));
+
+        buffer.toggle_comment("//");
+
+        assert_eq!(raw_text(&buffer.content), "  one\n  // two");
+        assert_eq!(buffer.content.cursor_position(), (0, 2));
+    }
+
+    #[test]
+    fn comments_out_the_last_line() {
+        let mut buffer = buffer("one\ntwo", (1, 3));
+
+        buffer.toggle_comment("#");
+
+        assert_eq!(raw_text(&buffer.content), "one\n# two");
+        assert_eq!(buffer.content.cursor_position(), (1, 5));
+    }
+
+    #[test]
+    fn leaves_an_empty_buffer_uncommented() {
+        let mut buffer = buffer("", (0, 0));
+
+        buffer.toggle_comment("//");
+
+        assert_eq!(raw_text(&buffer.content), "");
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn joins_the_cursor_line_with_the_next_one() {
+        let mut buffer = buffer("one  \n   two\nthree", (0, 1));
+
+        buffer.join_lines();
+
+        assert_eq!(raw_text(&buffer.content), "one two\nthree");
+        assert_eq!(buffer.content.cursor_position(), (0, 3));
+    }
+
+    #[test]
+    fn joins_selected_lines_skipping_blank_ones() {
+        let mut buffer = buffer("one\n\ntwo\nthree", (0, 0));
+        buffer.select_range((0, 0), (2, 3));
+
+        buffer.join_lines();
+
+        assert_eq!(raw_text(&buffer.content), "one two\nthree");
+        assert_eq!(buffer.content.cursor_position(), (0, 3));
+    }
+
+    #[test]
+    fn joins_nothing_on_the_last_line() {
+        let mut buffer = buffer("one\ntwo", (1, 1));
+
+        buffer.join_lines();
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo");
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn joins_nothing_in_an_empty_buffer() {
+        let mut buffer = buffer("", (0, 0));
+
+        buffer.join_lines();
+
+        assert_eq!(raw_text(&buffer.content), "");
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn sorts_selected_lines_and_selects_them() {
+        let mut buffer = buffer("b\na\nA\nc", (0, 0));
+        buffer.select_range((0, 0), (3, 1));
+
+        buffer.sort_lines(Sort::default());
+
+        assert_eq!(raw_text(&buffer.content), "A\na\nb\nc");
+        assert_eq!(buffer.content.selection().as_deref(), Some("A\na\nb\nc"));
+    }
+
+    #[test]
+    fn sorts_ignoring_case_keeping_equal_lines_in_order() {
+        let mut buffer = buffer("b\na\nA\nc", (0, 0));
+        buffer.select_range((0, 0), (3, 1));
+
+        buffer.sort_lines(Sort {
+            reverse: false,
+            ignore_case: true,
+        });
+
+        assert_eq!(raw_text(&buffer.content), "a\nA\nb\nc");
+    }
+
+    #[test]
+    fn sorts_in_reverse() {
+        let mut buffer = buffer("b\na\nc", (0, 0));
+        buffer.select_range((0, 0), (2, 1));
+
+        buffer.sort_lines(Sort {
+            reverse: true,
+            ignore_case: false,
+        });
+
+        assert_eq!(raw_text(&buffer.content), "c\nb\na");
+    }
+
+    #[test]
+    fn keeps_the_final_newline_last_when_sorting() {
+        let mut buffer = buffer("b\na\n", (0, 0));
+        buffer.select_range((0, 0), (2, 0));
+
+        buffer.sort_lines(Sort::default());
+
+        assert_eq!(raw_text(&buffer.content), "a\nb\n");
+        assert_eq!(buffer.content.cursor_position(), (1, 1));
+    }
+
+    #[test]
+    fn sorts_nothing_in_an_empty_buffer() {
+        let mut buffer = buffer("", (0, 0));
+
+        buffer.sort_lines(Sort::default());
+
+        assert_eq!(raw_text(&buffer.content), "");
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn deletes_the_cursor_line() {
+        let mut buffer = buffer("one\ntwo\nthree", (1, 2));
+
+        buffer.delete_lines();
+
+        assert_eq!(raw_text(&buffer.content), "one\nthree");
+        assert_eq!(buffer.content.cursor_position(), (1, 2));
+    }
+
+    #[test]
+    fn deletes_every_selected_line() {
+        let mut buffer = buffer("one\ntwo\nthree\n", (0, 0));
+        buffer.select_range((0, 1), (1, 2));
+
+        buffer.delete_lines();
+
+        assert_eq!(raw_text(&buffer.content), "three\n");
+        assert_eq!(buffer.content.cursor_position(), (0, 2));
+    }
+
+    #[test]
+    fn deletes_the_last_line_with_the_line_break_before_it() {
+        let mut buffer = buffer("one\ntwo", (1, 1));
+
+        buffer.delete_lines();
+
+        assert_eq!(raw_text(&buffer.content), "one");
+        assert_eq!(buffer.content.cursor_position(), (0, 1));
+    }
+
+    #[test]
+    fn deletes_the_only_line() {
+        let mut buffer = buffer("one", (0, 2));
+
+        buffer.delete_lines();
+
+        assert_eq!(raw_text(&buffer.content), "");
+        assert_eq!(buffer.content.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn duplicates_the_cursor_line_below_itself() {
+        let mut buffer = buffer("one\ntwo\n", (0, 2));
+
+        buffer.duplicate();
+
+        assert_eq!(raw_text(&buffer.content), "one\none\ntwo\n");
+        assert_eq!(buffer.content.cursor_position(), (1, 2));
+    }
+
+    #[test]
+    fn duplicates_the_last_line_without_a_final_newline() {
+        let mut buffer = buffer("one\ntwo", (1, 3));
+
+        buffer.duplicate();
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo\ntwo");
+        assert_eq!(buffer.content.cursor_position(), (2, 3));
+    }
+
+    #[test]
+    fn duplicates_the_selection_and_selects_the_copy() {
+        let mut buffer = buffer("one two", (0, 0));
+        buffer.select(0, 0, 4);
+
+        buffer.duplicate();
+
+        assert_eq!(raw_text(&buffer.content), "one one two");
+        assert_eq!(buffer.content.cursor_position(), (0, 8));
+        assert_eq!(buffer.content.selection().as_deref(), Some("one "));
+    }
+
+    #[test]
+    fn duplicates_the_line_of_an_empty_buffer() {
+        let mut buffer = buffer("", (0, 0));
+
+        buffer.duplicate();
+
+        assert_eq!(raw_text(&buffer.content), "\n");
+        assert_eq!(buffer.content.cursor_position(), (1, 0));
+    }
+
+    #[test]
+    fn changes_the_case_of_the_word_at_the_cursor() {
+        let mut buffer = buffer("hello world", (0, 8));
+
+        buffer.change_case(Case::Upper);
+
+        assert_eq!(raw_text(&buffer.content), "hello WORLD");
+        assert_eq!(buffer.content.selection().as_deref(), Some("WORLD"));
+    }
+
+    #[test]
+    fn changes_the_selection_to_title_case() {
+        let mut buffer = buffer("hELLO wORLD", (0, 0));
+        buffer.select(0, 0, 11);
+
+        buffer.change_case(Case::Title);
+
+        assert_eq!(raw_text(&buffer.content), "Hello World");
+        assert_eq!(buffer.content.cursor_position(), (0, 11));
+    }
+
+    #[test]
+    fn changes_the_case_of_the_word_ending_the_text() {
+        let mut buffer = buffer("one\nTWO", (1, 3));
+
+        buffer.change_case(Case::Lower);
+
+        assert_eq!(raw_text(&buffer.content), "one\ntwo");
+        assert_eq!(buffer.content.cursor_position(), (1, 3));
+    }
+
+    #[test]
+    fn changes_no_case_in_an_empty_buffer() {
+        let mut buffer = buffer("", (0, 0));
+
+        buffer.change_case(Case::Upper);
+
+        assert_eq!(raw_text(&buffer.content), "");
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn can_be_edited_after_saving_an_unwritable_file_elsewhere() {
+        let mut buffer = buffer("one", (0, 3));
+        buffer.read_only = true;
+        buffer.is_writable = false;
+
+        let fingerprint = buffer.fingerprint("one");
+        buffer.mark_saved("copy.txt".into(), None, fingerprint);
+
+        assert!(!buffer.read_only);
+        assert!(!buffer.is_dirty);
+    }
+
+    #[test]
+    fn stays_read_only_when_made_so_by_hand() {
+        let mut buffer = buffer("one", (0, 3));
+        buffer.read_only = true;
+
+        let fingerprint = buffer.fingerprint("one");
+        buffer.mark_saved("one.txt".into(), None, fingerprint);
+
+        assert!(buffer.read_only);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{io, path::PathBuf};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Settings picked by the user, kept in a TOML file that can also be edited
+/// by hand.
+///
+/// Settings left out of the file keep their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub dark_mode: Option<bool>,
+    /// The name of the syntax highlighting theme, as shown in the picker.
+    pub highlighter_theme: Option<String>,
+    /// The size of the editor text, as last zoomed to.
+    pub font_size: Option<f32>,
+    pub font_family: Option<String>,
+    /// Font families offered besides the installed monospace ones, for fonts
+    /// that don't say they are monospace.
+    pub font_families: Vec<String>,
+    pub use_spaces: Option<bool>,
+    pub tab_width: Option<usize>,
+    pub auto_indent: Option<bool>,
+    pub autosave: bool,
+    /// Seconds between autosaves.
+    pub autosave_interval: Option<u64>,
+    pub backup: bool,
+    pub trim_whitespace: bool,
+    pub final_newline: Option<bool>,
+    /// Whether the files open when the editor was closed are opened again.
+    pub restore_session: Option<bool>,
+    /// How inserted timestamps are written, in `strftime` syntax.
+    pub timestamp_format: Option<String>,
+}
+
+impl Config {
+    /// Loads the configuration from disk. A missing file gives the defaults,
+    /// and one that can't be read or parsed gives a message saying so.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = config_file() else {
+            return Ok(Config::default());
+        };
+
+        let toml = match std::fs::read_to_string(&path) {
+            Ok(toml) => toml,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(error) => return Err(format!("Couldn't read {}: {error}", path.display())),
+        };
+
+        toml::from_str(&toml).map_err(|error| {
+            format!(
+                "{} is invalid, using the defaults: {}",
+                path.display(),
+                error.message()
+            )
+        })
+    }
+
+    pub async fn save(self) -> Result<(), Error> {
+        let Some(path) = config_file() else {
+            return Ok(());
+        };
+
+        let toml = toml::to_string_pretty(&self)
+            .map_err(|_| Error::IoError(io::ErrorKind::InvalidData))?;
+
+        if let Some(directory) = path.parent() {
+            tokio::fs::create_dir_all(directory)
+                .await
+                .map_err(|error| Error::IoError(error.kind()))?;
+        }
+
+        crate::atomic::write(&path, toml.as_bytes())
+            .await
+            .map_err(|error| Error::IoError(error.kind()))
+    }
+}
+
+fn config_file() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "rio-editor").map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn round_trips_through_toml() {
+        let config = Config {
+            dark_mode: Some(false),
+            highlighter_theme: Some(String::from("Solarized Dark")),
+            font_size: Some(18.0),
+            font_families: vec![String::from("Iosevka")],
+            tab_width: Some(2),
+            use_spaces: Some(false),
+            autosave: true,
+            autosave_interval: Some(60),
+            trim_whitespace: true,
+            timestamp_format: Some(String::from("%H:%M")),
+            ..Config::default()
+        };
+
+        let toml = toml::to_string_pretty(&config).unwrap();
+
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn defaults_round_trip_through_toml() {
+        let toml = toml::to_string_pretty(&Config::default()).unwrap();
+
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn missing_settings_keep_their_defaults() {
+        let config: Config = toml::from_str("autosave = true\n").unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                autosave: true,
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_settings_of_the_wrong_type() {
+        assert!(toml::from_str::<Config>("font_size = \"big\"\n").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use iced::widget::text_editor::{Action, Content, Motion};
+
+use crate::buffer;
+
+/// Moves the cursor of `content` to the given line and column, clamping both
+/// to the text that is actually there.
+///
+/// The editor only understands motions, so this walks the cursor down line by
+/// line and then right character by character. Walking down (rather than
+/// counting newlines) keeps it correct when long lines are wrapped.
+pub fn move_to(content: &mut Content, line: usize, column: usize) {
+    content.edit(Action::Move(Motion::DocumentStart));
+
+    while content.cursor_position().0 < line {
+        let before = content.cursor_position();
+
+        content.edit(Action::Move(Motion::Down));
+
+        if content.cursor_position() == before {
+            break;
+        }
+    }
+
+    content.edit(Action::Move(Motion::Home));
+
+    let line = content.cursor_position().0;
+    let steps = content
+        .line(line)
+        .map(|text| characters_before(&text, column))
+        .unwrap_or_default();
+
+    for _ in 0..steps {
+        content.edit(Action::Move(Motion::Right));
+    }
+}
+
+/// Selects the text between byte columns `start` and `end` of `line`,
+/// leaving the cursor at the end of the selection.
+pub fn select(content: &mut Content, line: usize, start: usize, end: usize) {
+    move_to(content, line, start);
+
+    let length = content
+        .line(line)
+        .map(|text| characters_before(&text, end) - characters_before(&text, start))
+        .unwrap_or_default();
+
+    for _ in 0..length {
+        content.edit(Action::Select(Motion::Right));
+    }
+}
+
+/// The `(line, column)` of the end of the selection the cursor is not at,
+/// or `None` if nothing is selected.
+///
+/// The editor only tells where the cursor is and what is selected, so the
+/// selected text is looked for right before the cursor and then right after
+/// it.
+pub fn selection_anchor(content: &Content) -> Option<(usize, usize)> {
+    let selection = content.selection()?;
+    let text = buffer::raw_text(content);
+    let cursor = offset(&text, content.cursor_position());
+
+    let anchor = if text[..cursor].ends_with(&selection) {
+        cursor - selection.len()
+    } else if text[cursor..].starts_with(&selection) {
+        cursor + selection.len()
+    } else {
+        return None;
+    };
+
+    Some(position(&text, anchor))
+}
+
+/// Selects the text between the `(line, column)` positions `anchor` and
+/// `head`, which may span several lines, leaving the cursor at `head`.
+pub fn select_range(content: &mut Content, anchor: (usize, usize), head: (usize, usize)) {
+    move_to(content, anchor.0, anchor.1);
+
+    let text = buffer::raw_text(content);
+    let (start, end) = (offset(&text, anchor), offset(&text, head));
+    let (motion, range) = if start <= end {
+        (Motion::Right, start..end)
+    } else {
+        (Motion::Left, end..start)
+    };
+
+    // Moving past the end of a line takes the cursor to the start of the
+    // next one, so every line break is a single step.
+    for _ in 0..text[range].chars().count() {
+        content.edit(Action::Select(motion));
+    }
+}
+
+/// The byte offset into `text` of a `(line, column)` position, clamped to
+/// the text that is actually there.
+pub fn offset(text: &str, (line, column): (usize, usize)) -> usize {
+    let start: usize = text
+        .split('\n')
+        .take(line)
+        .map(|line| line.len() + 1)
+        .sum::<usize>()
+        .min(text.len());
+    let line = text[start..].split('\n').next().unwrap_or_default();
+
+    start + column_of(line, characters_before(line, column))
+}
+
+/// The `(line, column)` position of byte `offset` into `text`.
+pub fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    (before.matches('\n').count(), offset - line_start)
+}
+
+/// The number of characters before byte `column` of `line`, clamped to the
+/// end of the line.
+pub fn characters_before(line: &str, column: usize) -> usize {
+    let mut end = column.min(line.len());
+
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    line[..end].chars().count()
+}
+
+/// Parses a 1-based `line` or `line:column` position as typed by the user,
+/// returning it 0-based.
+pub fn parse_position(input: &str) -> Option<(usize, usize)> {
+    let (line, column) = match input.trim().split_once(':') {
+        Some((line, column)) => (line, Some(column)),
+        None => (input.trim(), None),
+    };
+
+    let line = line.trim().parse::<usize>().ok()?.saturating_sub(1);
+    let column = match column {
+        Some(column) => column.trim().parse::<usize>().ok()?.saturating_sub(1),
+        None => 0,
+    };
+
+    Some((line, column))
+}
+
+/// The byte column of the character at index `character` of `line`, clamped
+/// to the end of the line.
+pub fn column_of(line: &str, character: usize) -> usize {
+    line.char_indices()
+        .nth(character)
+        .map_or(line.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::widget::text_editor::Content;
+
+    use super::{move_to, parse_position};
+
+    #[test]
+    fn parses_a_line() {
+        assert_eq!(parse_position("12"), Some((11, 0)));
+    }
+
+    #[test]
+    fn parses_a_line_and_column() {
+        assert_eq!(parse_position(" 3 : 4 "), Some((2, 3)));
+    }
+
+    #[test]
+    fn parses_line_zero_as_the_first_line() {
+        assert_eq!(parse_position("0:0"), Some((0, 0)));
+    }
+
+    #[test]
+    fn rejects_empty_and_partial_positions() {
+        assert_eq!(parse_position(""), None);
+        assert_eq!(parse_position("  "), None);
+        assert_eq!(parse_position(":4"), None);
+        assert_eq!(parse_position("3:"), None);
+        assert_eq!(parse_position("three"), None);
+    }
+
+    #[test]
+    fn clamps_moves_past_the_end_of_the_text() {
+        let mut content = Content::with("one\ntwo");
+
+        move_to(&mut content, 9, 9);
+        assert_eq!(content.cursor_position(), (1, 3));
+
+        move_to(&mut content, 0, 9);
+        assert_eq!(content.cursor_position(), (0, 3));
+    }
+
+    #[test]
+    fn clamps_moves_inside_a_character_to_its_start() {
+        let mut content = Content::with("héllo");
+
+        move_to(&mut content, 0, 2);
+
+        assert_eq!(content.cursor_position(), (0, 1));
+    }
+
+    #[test]
+    fn moves_in_an_empty_text() {
+        let mut content = Content::with("");
+
+        move_to(&mut content, 3, 3);
+
+        assert_eq!(content.cursor_position(), (0, 0));
+    }
+}
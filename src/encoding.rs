@@ -0,0 +1,247 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// How many leading bytes are checked for NUL bytes, which text files in
+/// encodings without a BOM never contain.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+/// The encoding a file was read with, restored when it is written back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding {
+    pub encoding: &'static Encoding,
+    pub has_bom: bool,
+}
+
+impl TextEncoding {
+    /// The encodings offered in the picker, along with `current` if it is
+    /// not one of them.
+    pub fn choices(current: TextEncoding) -> Vec<TextEncoding> {
+        let mut choices = vec![
+            TextEncoding::new(UTF_8, false),
+            TextEncoding::new(UTF_8, true),
+            TextEncoding::new(UTF_16LE, true),
+            TextEncoding::new(UTF_16BE, true),
+            TextEncoding::new(WINDOWS_1252, false),
+        ];
+
+        if !choices.contains(&current) {
+            choices.push(current);
+        }
+
+        choices
+    }
+
+    /// Parses an encoding as it is displayed, like "UTF-8 BOM".
+    pub fn from_name(name: &str) -> Option<TextEncoding> {
+        let (label, has_bom) = match name.strip_suffix(" BOM") {
+            Some(label) => (label, true),
+            None => (name, false),
+        };
+
+        Encoding::for_label(label.as_bytes()).map(|encoding| TextEncoding::new(encoding, has_bom))
+    }
+
+    fn new(encoding: &'static Encoding, has_bom: bool) -> Self {
+        TextEncoding { encoding, has_bom }
+    }
+}
+
+impl Hash for TextEncoding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.encoding.name().hash(state);
+        self.has_bom.hash(state);
+    }
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        TextEncoding {
+            encoding: UTF_8,
+            has_bom: false,
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.has_bom {
+            write!(f, "{} BOM", self.encoding.name())
+        } else {
+            write!(f, "{}", self.encoding.name())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DecodeError {
+    /// The bytes don't look like text at all.
+    Binary,
+    /// The bytes contain sequences that are invalid in the detected encoding.
+    Malformed(&'static Encoding),
+}
+
+/// Decodes `bytes`, picking the encoding from a BOM, falling back to UTF-8
+/// and then to a guess based on the contents.
+///
+/// Unless `lossy` is set, invalid sequences are an error rather than being
+/// replaced.
+pub fn decode(bytes: &[u8], lossy: bool) -> Result<(String, TextEncoding), DecodeError> {
+    let (encoding, has_bom) = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => (encoding, true),
+        None if std::str::from_utf8(bytes).is_ok() => (UTF_8, false),
+        None if is_binary(bytes) => return Err(DecodeError::Binary),
+        None => {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(bytes, true);
+
+            (detector.guess(None, true), false)
+        }
+    };
+
+    let (text, had_errors) = if has_bom {
+        encoding.decode_with_bom_removal(bytes)
+    } else {
+        encoding.decode_without_bom_handling(bytes)
+    };
+
+    if had_errors && !lossy {
+        return Err(DecodeError::Malformed(encoding));
+    }
+
+    Ok((text.into_owned(), TextEncoding { encoding, has_bom }))
+}
+
+/// Decodes `bytes` as `encoding` no matter what they look like, replacing
+/// invalid sequences and dropping a BOM of that encoding.
+pub fn decode_as(bytes: &[u8], encoding: TextEncoding) -> String {
+    let (text, _) = encoding.encoding.decode_with_bom_removal(bytes);
+
+    text.into_owned()
+}
+
+/// Encodes `text`, failing with the first character the encoding can't
+/// represent instead of replacing it.
+pub fn encode(text: &str, encoding: TextEncoding) -> Result<Vec<u8>, char> {
+    let TextEncoding { encoding, has_bom } = encoding;
+
+    // `encoding_rs` only decodes UTF-16, so it is encoded by hand.
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let is_le = encoding == UTF_16LE;
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+
+        for unit in has_bom
+            .then_some(0xFEFF)
+            .into_iter()
+            .chain(text.encode_utf16())
+        {
+            if is_le {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+
+        return Ok(bytes);
+    }
+
+    let (encoded, _, has_unmappable) = encoding.encode(text);
+
+    if has_unmappable {
+        let mut buffer = [0; 4];
+
+        return Err(text
+            .chars()
+            .find(|c| encoding.encode(c.encode_utf8(&mut buffer)).2)
+            .unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len() + 3);
+
+    if has_bom && encoding == UTF_8 {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+
+    bytes.extend_from_slice(&encoded);
+
+    Ok(bytes)
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .take(BINARY_CHECK_LENGTH)
+        .any(|&byte| byte == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use super::{decode, decode_as, encode, DecodeError, TextEncoding};
+
+    const TEXT: &str = "café €5\r\nnaïve\n";
+
+    fn round_trip(encoding: &'static Encoding, bom: &[u8]) {
+        let encoding = TextEncoding {
+            encoding,
+            has_bom: true,
+        };
+        let bytes = encode(TEXT, encoding).unwrap();
+
+        assert!(bytes.starts_with(bom));
+        assert_eq!(decode(&bytes, false).unwrap(), (TEXT.to_string(), encoding));
+    }
+
+    #[test]
+    fn round_trips_windows_1252() {
+        let encoding = TextEncoding {
+            encoding: WINDOWS_1252,
+            has_bom: false,
+        };
+        let bytes = encode(TEXT, encoding).unwrap();
+
+        assert_eq!(&bytes[..7], b"caf\xE9 \x805");
+        assert_eq!(decode_as(&bytes, encoding), TEXT);
+    }
+
+    #[test]
+    fn round_trips_utf_16_with_its_bom() {
+        round_trip(UTF_16LE, b"\xFF\xFE");
+        round_trip(UTF_16BE, b"\xFE\xFF");
+    }
+
+    #[test]
+    fn round_trips_utf_8_with_its_bom() {
+        round_trip(UTF_8, b"\xEF\xBB\xBF");
+    }
+
+    #[test]
+    fn reports_the_first_character_windows_1252_cannot_represent() {
+        let encoding = TextEncoding {
+            encoding: WINDOWS_1252,
+            has_bom: false,
+        };
+
+        assert_eq!(encode("café 日本", encoding), Err('日'));
+    }
+
+    #[test]
+    fn detects_binary_files() {
+        assert!(matches!(
+            decode(b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR\xFF", false),
+            Err(DecodeError::Binary)
+        ));
+    }
+
+    #[test]
+    fn decodes_utf_8_without_a_bom() {
+        assert_eq!(
+            decode(TEXT.as_bytes(), false).unwrap(),
+            (TEXT.to_string(), TextEncoding::default())
+        );
+    }
+}
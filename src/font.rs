@@ -0,0 +1,118 @@
+use std::collections::BTreeSet;
+
+use iced::Font;
+
+/// The name shown for the built-in monospace font.
+pub const DEFAULT: &str = "Monospace";
+
+/// The font families installed on the system.
+#[derive(Debug, Clone, Default)]
+pub struct Installed {
+    /// The monospace ones, offered in the font picker.
+    monospace: BTreeSet<String>,
+    all: BTreeSet<String>,
+}
+
+impl Installed {
+    pub fn contains(&self, family: &str) -> bool {
+        family == DEFAULT || self.all.contains(family)
+    }
+}
+
+/// Looks up the installed fonts, which reads every one of them and so is
+/// done away from the interface.
+pub async fn installed() -> Installed {
+    tokio::task::spawn_blocking(|| {
+        let mut database = fontdb::Database::new();
+        database.load_system_fonts();
+
+        let mut installed = Installed::default();
+
+        for face in database.faces() {
+            for (family, _) in &face.families {
+                if face.monospaced {
+                    installed.monospace.insert(family.clone());
+                }
+
+                installed.all.insert(family.clone());
+            }
+        }
+
+        installed
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// The families offered in the picker: the built-in one, the installed
+/// monospace ones, and the `extra` ones from the configuration that are
+/// installed too.
+pub fn families(installed: &Installed, extra: &[String]) -> Vec<&'static str> {
+    let mut families = vec![DEFAULT];
+    let extra = extra
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| installed.contains(name));
+
+    for name in installed.monospace.iter().map(String::as_str).chain(extra) {
+        if !families.iter().any(|family| *family == name) {
+            families.push(leak(name));
+        }
+    }
+
+    families
+}
+
+/// A name for `family` that lives as long as the editor, as fonts are looked
+/// up by. Only done once per family, when the installed fonts are known.
+pub fn leak(family: &str) -> &'static str {
+    if family == DEFAULT {
+        DEFAULT
+    } else {
+        Box::leak(family.to_owned().into_boxed_str())
+    }
+}
+
+pub fn font(family: &'static str) -> Font {
+    if family == DEFAULT {
+        Font::MONOSPACE
+    } else {
+        Font::with_name(family)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{families, Installed, DEFAULT};
+
+    fn installed() -> Installed {
+        Installed {
+            monospace: ["Fira Code", "Hack"].map(String::from).into(),
+            all: ["Fira Code", "Hack", "Noto Sans"].map(String::from).into(),
+        }
+    }
+
+    #[test]
+    fn offers_the_installed_monospace_families() {
+        assert_eq!(families(&installed(), &[]), [DEFAULT, "Fira Code", "Hack"]);
+    }
+
+    #[test]
+    fn offers_extra_families_only_if_they_are_installed() {
+        let extra = ["Noto Sans", "Consolas", " Hack "].map(String::from);
+
+        assert_eq!(
+            families(&installed(), &extra),
+            [DEFAULT, "Fira Code", "Hack", "Noto Sans"]
+        );
+    }
+
+    #[test]
+    fn always_has_the_default_family() {
+        let installed = Installed::default();
+
+        assert!(installed.contains(DEFAULT));
+        assert!(!installed.contains("Hack"));
+        assert_eq!(families(&installed, &[]), [DEFAULT]);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+
+/// How many undo steps a buffer keeps before dropping the oldest ones.
+pub const DEFAULT_LIMIT: usize = 200;
+
+/// How many bytes of text the undo steps of a buffer may take up together,
+/// so huge files keep fewer of them. The latest step is always kept.
+pub const DEFAULT_BYTE_LIMIT: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub text: String,
+    pub cursor: (usize, usize),
+}
+
+/// The kind of a single keystroke, which decides whether it joins the step
+/// of the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stroke {
+    /// A character that is part of a word.
+    Word,
+    /// A space, a tab or a line break.
+    Space,
+    /// Backspace or Delete.
+    Delete,
+}
+
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    limit: usize,
+    byte_limit: usize,
+    /// The bytes of text the undo steps take up.
+    bytes: usize,
+    /// The last keystroke of the current step, until it is sealed.
+    stroke: Option<Stroke>,
+}
+
+impl History {
+    pub fn new(limit: usize, byte_limit: usize) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+            byte_limit,
+            bytes: 0,
+            stroke: None,
+        }
+    }
+
+    /// Records the state right before an edit, made by `stroke` if it was a
+    /// single keystroke.
+    ///
+    /// Consecutive keystrokes are grouped into a single step, so undo removes
+    /// a word and the spaces after it at a time instead of a letter at a
+    /// time, and brings back a run of deleted text all at once.
+    pub fn record(&mut self, stroke: Option<Stroke>, snapshot: impl FnOnce() -> Snapshot) {
+        self.redo.clear();
+
+        let joins = matches!(
+            (self.stroke, stroke),
+            (Some(Stroke::Word), Some(Stroke::Word | Stroke::Space))
+                | (Some(Stroke::Space), Some(Stroke::Space))
+                | (Some(Stroke::Delete), Some(Stroke::Delete))
+        );
+
+        if !joins {
+            self.push(snapshot());
+        }
+
+        self.stroke = stroke;
+    }
+
+    /// Ends the current group of keystrokes.
+    pub fn seal(&mut self) {
+        self.stroke = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let snapshot = self.undo.pop_back()?;
+
+        self.bytes -= snapshot.text.len();
+        self.redo.push(current);
+        self.stroke = None;
+
+        Some(snapshot)
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let snapshot = self.redo.pop()?;
+
+        self.push(current);
+        self.stroke = None;
+
+        Some(snapshot)
+    }
+
+    /// Adds an undo step, dropping the oldest ones past the limits.
+    fn push(&mut self, snapshot: Snapshot) {
+        self.bytes += snapshot.text.len();
+        self.undo.push_back(snapshot);
+
+        while self.undo.len() > 1 && (self.undo.len() > self.limit || self.bytes > self.byte_limit)
+        {
+            if let Some(oldest) = self.undo.pop_front() {
+                self.bytes -= oldest.text.len();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, Snapshot, Stroke};
+
+    fn snapshot(text: &str) -> Snapshot {
+        Snapshot {
+            text: text.to_owned(),
+            cursor: (0, 0),
+        }
+    }
+
+    fn undo_all(history: &mut History) -> Vec<String> {
+        std::iter::from_fn(|| history.undo(snapshot("")).map(|snapshot| snapshot.text)).collect()
+    }
+
+    #[test]
+    fn groups_a_word_with_the_spaces_after_it() {
+        let mut history = History::new(10, usize::MAX);
+
+        for (stroke, text) in [
+            (Stroke::Word, ""),
+            (Stroke::Word, "a"),
+            (Stroke::Space, "ab"),
+            (Stroke::Word, "ab "),
+            (Stroke::Word, "ab c"),
+        ] {
+            history.record(Some(stroke), || snapshot(text));
+        }
+
+        assert_eq!(undo_all(&mut history), ["ab ", ""]);
+    }
+
+    #[test]
+    fn groups_deletions() {
+        let mut history = History::new(10, usize::MAX);
+
+        history.record(Some(Stroke::Word), || snapshot(""));
+        history.record(Some(Stroke::Delete), || snapshot("abc"));
+        history.record(Some(Stroke::Delete), || snapshot("ab"));
+        history.record(Some(Stroke::Delete), || snapshot("a"));
+
+        assert_eq!(undo_all(&mut history), ["abc", ""]);
+    }
+
+    #[test]
+    fn seals_the_current_group() {
+        let mut history = History::new(10, usize::MAX);
+
+        history.record(Some(Stroke::Word), || snapshot(""));
+        history.seal();
+        history.record(Some(Stroke::Word), || snapshot("a"));
+
+        assert_eq!(undo_all(&mut history), ["a", ""]);
+    }
+
+    #[test]
+    fn keeps_every_other_edit_apart() {
+        let mut history = History::new(10, usize::MAX);
+
+        history.record(None, || snapshot(""));
+        history.record(None, || snapshot("a"));
+
+        assert_eq!(undo_all(&mut history), ["a", ""]);
+    }
+
+    #[test]
+    fn drops_the_oldest_steps_past_the_limit() {
+        let mut history = History::new(2, usize::MAX);
+
+        for text in ["a", "b", "c"] {
+            history.record(None, || snapshot(text));
+        }
+
+        assert_eq!(undo_all(&mut history), ["c", "b"]);
+    }
+
+    #[test]
+    fn drops_the_oldest_steps_past_the_byte_limit() {
+        let mut history = History::new(10, 6);
+
+        for text in ["aaa", "bbb", "ccc"] {
+            history.record(None, || snapshot(text));
+        }
+
+        assert_eq!(undo_all(&mut history), ["ccc", "bbb"]);
+    }
+
+    #[test]
+    fn keeps_the_latest_step_past_the_byte_limit() {
+        let mut history = History::new(10, 2);
+
+        history.record(None, || snapshot("aaa"));
+
+        assert_eq!(undo_all(&mut history), ["aaa"]);
+    }
+}
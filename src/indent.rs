@@ -0,0 +1,124 @@
+use std::fmt;
+
+pub const DEFAULT_WIDTH: usize = 4;
+pub const WIDTHS: [usize; 3] = [2, 4, 8];
+
+/// How many lines are looked at for mixed indentation, so that opening a
+/// huge file does not stall on it.
+const SCAN_LINES: usize = 5000;
+
+/// How one level of indentation is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indent {
+    /// Whether Tab inserts spaces up to the next tab stop instead of a tab.
+    pub use_spaces: bool,
+    /// How many columns one level spans.
+    pub width: usize,
+}
+
+impl Indent {
+    /// Every indentation offered in the status bar, spaces first.
+    pub fn choices() -> Vec<Indent> {
+        [true, false]
+            .into_iter()
+            .flat_map(|use_spaces| WIDTHS.map(|width| Indent { use_spaces, width }))
+            .collect()
+    }
+
+    /// The text of one level of indentation.
+    pub fn level(self) -> String {
+        if self.use_spaces {
+            " ".repeat(self.width)
+        } else {
+            String::from("\t")
+        }
+    }
+
+    /// The length in bytes of the first level of indentation at the start
+    /// of `line`, which may mix tabs and spaces and is cut short by the first
+    /// tab stop or the end of the whitespace.
+    pub fn leading_level(self, line: &str) -> usize {
+        let mut columns = 0;
+        let mut length = 0;
+
+        for byte in line.bytes() {
+            match byte {
+                b' ' => columns += 1,
+                b'\t' => columns += self.width - columns % self.width,
+                _ => break,
+            }
+
+            length += 1;
+
+            if columns >= self.width {
+                break;
+            }
+        }
+
+        length
+    }
+
+    /// `line` with its leading whitespace rewritten to span the same columns
+    /// in this indentation.
+    pub fn normalize(self, line: &str) -> String {
+        let rest = line.trim_start_matches([' ', '\t']);
+        let mut columns = 0;
+
+        for byte in line[..line.len() - rest.len()].bytes() {
+            match byte {
+                b'\t' => columns += self.width - columns % self.width,
+                _ => columns += 1,
+            }
+        }
+
+        let leading = if self.use_spaces {
+            " ".repeat(columns)
+        } else {
+            "\t".repeat(columns / self.width) + &" ".repeat(columns % self.width)
+        };
+
+        leading + rest
+    }
+}
+
+/// Whether the first lines of `text` are indented with tabs in some places
+/// and with spaces in others.
+///
+/// A single space after tabs is left out, as it lines up block comments
+/// rather than indenting.
+pub fn is_mixed(text: &str) -> bool {
+    let (mut tabs, mut spaces) = (false, false);
+
+    for line in text.lines().take(SCAN_LINES) {
+        let rest = line.trim_start_matches([' ', '\t']);
+        let leading = &line[..line.len() - rest.len()];
+
+        tabs |= leading.contains('\t');
+        spaces |= leading.contains("  ") || leading.starts_with(' ');
+
+        if tabs && spaces {
+            return true;
+        }
+    }
+
+    false
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent {
+            use_spaces: true,
+            width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.use_spaces {
+            write!(f, "Spaces: {}", self.width)
+        } else {
+            write!(f, "Tab Size: {}", self.width)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+/// Common file extensions and the names of the languages they hold.
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("Rust", &["rs"]),
+    ("Markdown", &["md", "markdown"]),
+    ("TOML", &["toml"]),
+    ("JSON", &["json"]),
+    ("YAML", &["yaml", "yml"]),
+    ("HTML", &["html", "htm"]),
+    ("CSS", &["css"]),
+    ("JavaScript", &["js", "mjs", "cjs"]),
+    ("TypeScript", &["ts", "tsx"]),
+    ("Python", &["py"]),
+    ("C", &["c", "h"]),
+    ("C++", &["cpp", "cc", "cxx", "hpp", "hh"]),
+    ("Go", &["go"]),
+    ("Java", &["java"]),
+    ("Shell", &["sh", "bash", "zsh"]),
+    ("SQL", &["sql"]),
+    ("XML", &["xml"]),
+    ("Lua", &["lua"]),
+    ("Ruby", &["rb"]),
+];
+
+/// The friendly name of the language stored in files with `extension`.
+pub fn name(extension: Option<&str>) -> &'static str {
+    extension
+        .and_then(find)
+        .map_or("Plain Text", |(name, _)| name)
+}
+
+/// The extension to hand to the highlighter for files with `extension`, or
+/// `None` if it isn't a known language and the text should stay plain.
+pub fn highlight_extension(extension: Option<&str>) -> Option<String> {
+    let extension = extension?;
+
+    find(extension).map(|_| extension.to_ascii_lowercase())
+}
+
+/// Whether `line` opens a block whose lines are indented one level deeper,
+/// in files with `extension`.
+pub fn opens_block(extension: Option<&str>, line: &str) -> bool {
+    let Some(last) = line.trim_end().chars().last() else {
+        return false;
+    };
+
+    match extension.and_then(find).map(|(name, _)| name) {
+        Some("Python" | "YAML") => last == ':',
+        Some("Markdown") | None => false,
+        Some(_) => matches!(last, '{' | '(' | '['),
+    }
+}
+
+/// The marker that starts a comment running to the end of the line, in
+/// files with `extension`.
+pub fn line_comment(extension: Option<&str>) -> Option<&'static str> {
+    match extension.and_then(find).map(|(name, _)| name)? {
+        "Rust" | "JavaScript" | "TypeScript" | "C" | "C++" | "Go" | "Java" => Some("//"),
+        "Python" | "TOML" | "YAML" | "Shell" | "Ruby" => Some("#"),
+        "SQL" | "Lua" => Some("--"),
+        _ => None,
+    }
+}
+
+fn find(extension: &str) -> Option<(&'static str, &'static [&'static str])> {
+    LANGUAGES.iter().copied().find(|(_, extensions)| {
+        extensions
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(extension))
+    })
+}
+
+/// An entry of the language picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    /// The extension the highlighter is told about, or `None` to detect it
+    /// from the file name.
+    pub extension: Option<&'static str>,
+}
+
+impl Language {
+    pub const AUTO: Language = Language {
+        name: "Auto Detect",
+        extension: None,
+    };
+
+    /// Every language that can be picked, starting with automatic detection.
+    pub fn all() -> Vec<Language> {
+        let languages = LANGUAGES.iter().map(|&(name, extensions)| Language {
+            name,
+            extension: extensions.first().copied(),
+        });
+
+        [Language::AUTO]
+            .into_iter()
+            .chain(languages)
+            .chain([Language {
+                name: "Plain Text",
+                extension: Some("txt"),
+            }])
+            .collect()
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
@@ -1,278 +1,3353 @@
+mod atomic;
+mod buffer;
+mod config;
+mod cursor;
+mod encoding;
+mod font;
+mod history;
+mod indent;
+mod language;
+mod line_ending;
+mod palette;
+mod search;
+mod session;
+mod template;
+
 use std::{
-    io,
+    collections::VecDeque,
+    fmt, io,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
 use iced::{
-    executor,
+    clipboard, event, executor,
+    futures::{channel::mpsc, future, never::Never, SinkExt, StreamExt},
     highlighter::{self, Highlighter},
-    keyboard, theme,
+    keyboard, mouse, subscription, theme, time,
     widget::{
-        button, column, container, horizontal_space, pick_list, row, text, text_editor, tooltip,
+        button, checkbox, column, container, horizontal_space, pick_list, row, scrollable, text,
+        text_editor, text_input, tooltip, Column, Row, Text,
     },
-    Application, Command, Element, Font, Length, Settings, Theme,
+    window, Alignment, Application, Color, Command, Element, Event, Font, Length, Settings,
+    Subscription, Theme,
 };
 
+use buffer::{Buffer, Case, FileStamp, Fingerprint, Sort};
+use config::Config;
+use encoding::TextEncoding;
+use indent::Indent;
+use language::Language;
+use line_ending::LineEnding;
+use palette::Palette;
+use search::Search;
+use session::Session;
+use template::TemplateKind;
+
 fn main() -> iced::Result {
+    // Quiet unless asked for, e.g. with `RUST_LOG=rio_editor=debug`.
+    env_logger::init();
+
+    let path = std::env::args_os().nth(1).map(|path| {
+        let path = PathBuf::from(path);
+
+        match std::env::current_dir() {
+            Ok(directory) => directory.join(path),
+            Err(_) => path,
+        }
+    });
+
+    let session = Session::load();
+
     Editor::run(Settings {
+        window: window_settings(&session),
+        flags: (path, session),
         default_font: Font::MONOSPACE,
         fonts: vec![include_bytes!("../fonts/rio-icons.ttf").as_slice().into()],
+        exit_on_close_request: false,
         ..Settings::default()
     })
 }
 
+/// The smallest window size restored from the session, so a window that was
+/// squashed comes back usable.
+const MIN_WINDOW_SIZE: (u32, u32) = (400, 300);
+/// How far from the origin a restored window may start, beyond which it is
+/// assumed to be on a monitor that is no longer there.
+const MAX_WINDOW_OFFSET: i32 = 8192;
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+const MIN_FONT_SIZE: f32 = 8.0;
+const MAX_FONT_SIZE: f32 = 48.0;
+/// How long a transient status message stays up.
+const STATUS_DURATION: Duration = Duration::from_secs(2);
+/// How long file system events are collected before reporting the files
+/// they touched, since a single save can produce a burst of them.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+const DEFAULT_AUTOSAVE_INTERVAL: u64 = 30;
+/// ISO 8601 in local time, with the offset from UTC.
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+/// The autosave intervals offered in the preferences, in seconds.
+const AUTOSAVE_INTERVALS: [u64; 4] = [10, 30, 60, 300];
+
 struct Editor {
-    content: text_editor::Content,
+    buffers: Vec<Buffer>,
+    active: usize,
+    pending: Option<PendingAction>,
+    /// The action waiting on the user to decide what to do with the unsaved
+    /// changes it would throw away.
+    confirming: Option<PendingAction>,
+    /// The buffers whose changes the user agreed to lose while quitting.
+    discarded: Vec<usize>,
+    /// Dropped or restored files waiting for the one before them to finish
+    /// loading.
+    dropped: VecDeque<PathBuf>,
+    /// Whether files are being dragged over the window.
+    is_hovering: bool,
+    search: Option<Search>,
+    go_to_line: Option<String>,
+    /// What is typed to insert at the start of the selected lines, while
+    /// asking for it.
+    line_prefix: Option<String>,
+    /// The command palette, while it is open.
+    palette: Option<Palette>,
     error: Option<Error>,
-    path: Option<PathBuf>,
-    theme: highlighter::Theme,
-    is_dirty: bool,
+    notice: Option<String>,
+    /// A message that is only shown for a moment, and when it was shown.
+    status_message: Option<(String, Instant)>,
+    theme: Theme,
+    highlighter_theme: highlighter::Theme,
+    font_size: f32,
+    font_family: &'static str,
+    /// The families offered in the font picker, once the installed fonts
+    /// are known.
+    font_families: Vec<&'static str>,
+    show_preferences: bool,
+    /// What is typed as the font size in the preferences, while it is being
+    /// edited.
+    font_size_input: Option<String>,
+    /// The indentation of buffers that don't pick their own.
+    indent: Indent,
+    /// Whether new lines start with the indentation of the line before.
+    auto_indent: bool,
+    autosave: bool,
+    /// Seconds between autosaves.
+    autosave_interval: u64,
+    /// Whether the file on disk is copied to `<name>.bak` before saving.
+    backup: bool,
+    /// Whether trailing whitespace is stripped from every line when saving.
+    trim_whitespace: bool,
+    /// Whether saved files always end with a line break.
+    final_newline: bool,
+    /// Whether the files open when the editor was closed are opened again on
+    /// the next launch.
+    restore_session: bool,
+    /// How inserted timestamps are written, in `strftime` syntax.
+    timestamp_format: String,
+    modifiers: keyboard::Modifiers,
+    config: Config,
+    config_writes: Writes,
+    session: Session,
+    session_writes: Writes,
+    /// Whether the window closes once the session is written.
+    is_closing: bool,
+    /// Whether keys no widget takes are typed into the buffer, because the
+    /// editor can't be given the keyboard back once a prompt closes.
+    keys_to_buffer: bool,
+    show_welcome: bool,
+    is_loading: bool,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     Edit(text_editor::Action),
+    Undo,
+    Redo,
+    SelectAll,
     New,
+    NewFromTemplate(TemplateKind),
+    TemplateLoaded((TemplateKind, String)),
     Open,
-    FileOpened(Result<(PathBuf, Arc<String>), Error>),
+    OpenRecent(PathBuf),
+    WindowResized(u32, u32),
+    WindowMoved(i32, i32),
+    FileHovered,
+    FilesHoveredLeft,
+    FileDropped(PathBuf),
+    Reload,
+    Revert,
+    ReopenWithEncoding(TextEncoding),
+    ReloadConfirmed(usize, TextEncoding, bool),
+    FileReloaded(usize, Result<OpenedFile, Error>),
+    FileOpened(Result<OpenedFile, Error>),
+    LossyOpenConfirmed(PathBuf, bool),
+    Save,
+    SaveAs,
+    OverwriteConfirmed(usize, bool),
+    /// Whether the mixed line endings of the buffer may be unified to save
+    /// it, and whether it is saved somewhere else.
+    LineEndingsConfirmed(usize, bool, bool),
+    SaveElsewhereConfirmed(usize, bool),
+    FileSaved(usize, Result<SavedFile, Error>),
+    /// An autosave finished, which never asks anything when it fails.
+    Autosaved(usize, Result<SavedFile, Error>),
+    FileChangedExternally(Vec<PathBuf>),
+    DiskChecked(Vec<(PathBuf, Option<FileStamp>)>),
+    KeepChanges,
+    ToggleFind,
+    FindQueryChanged(String),
+    FindNext,
+    FindPrevious,
+    RegexToggled(bool),
+    ReplacementChanged(String),
+    Replace,
+    ReplaceAll,
+    CloseFind,
+    OpenGoToLine,
+    GoToLineChanged(String),
+    GoToLine,
+    OpenLinePrefix,
+    LinePrefixChanged(String),
+    InsertLinePrefix,
+    Escape,
+    TogglePalette,
+    PaletteQueryChanged(String),
+    PaletteMoved(bool),
+    PaletteSubmitted,
+    RunCommand(Box<Message>),
+    TabSelected(usize),
+    TabClosed(usize),
+    Close,
+    CopyPath,
+    RevealInFolder,
+    Revealed(Result<(), Error>),
+    ToggleReadOnly,
+    ToggleComment,
+    Duplicate,
+    SortLines(Sort),
+    ChangeCase(Case),
+    JoinLines,
+    DeleteLines,
+    MoveLinesUp,
+    MoveLinesDown,
+    ConfirmDiscard(PendingAction, Choice),
+    SetLanguage(Option<String>),
+    IndentSelected(Indent),
+    NormalizeIndent,
+    ConvertLineEndings(LineEnding),
+    EncodingSelected(TextEncoding),
+    SelectHighlightTheme(highlighter::Theme),
+    ThemeChanged(Theme),
+    ToggleTheme,
+    FontSelected(&'static str),
+    FontsLoaded(font::Installed),
+    TogglePreferences,
+    FontSizeInputChanged(String),
+    UseSpacesToggled(bool),
+    TabWidthSelected(usize),
+    AutoIndentToggled(bool),
+    AutosaveToggled(bool),
+    AutosaveIntervalSelected(u64),
+    BackupToggled(bool),
+    TrimWhitespaceToggled(bool),
+    FinalNewlineToggled(bool),
+    RestoreSessionToggled(bool),
+    TimestampFormatChanged(String),
+    InsertTimestamp,
+    Autosave,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ModifiersChanged(keyboard::Modifiers),
+    WheelScrolled(mouse::ScrollDelta),
+    ClearStatus,
+    RefreshStats,
+    /// Redraws the status bar so the time since the file was modified stays
+    /// current.
+    RefreshFileInfo,
+    DismissError,
+    CloseRequested,
+    ConfigSaved(Result<(), Error>),
+    SessionSaved(Result<(), Error>),
+}
+
+#[derive(Debug, Clone)]
+struct OpenedFile {
+    path: PathBuf,
+    text: Arc<String>,
+    encoding: TextEncoding,
+    stamp: Option<FileStamp>,
+    is_writable: bool,
+}
+
+#[derive(Debug, Clone)]
+struct SavedFile {
+    path: PathBuf,
+    stamp: Option<FileStamp>,
+    /// The text that was written, which edits made while it was being
+    /// written don't change.
+    fingerprint: Fingerprint,
+    /// Why the backup of the previous version couldn't be made.
+    backup_error: Option<io::ErrorKind>,
+}
+
+/// Writes of a file that run in the background, one at a time, followed by
+/// another one if it changed in the meantime.
+#[derive(Debug, Default)]
+struct Writes {
+    in_flight: bool,
+    outdated: bool,
+}
+
+impl Writes {
+    /// Whether a write can start now. Otherwise the running one is followed
+    /// by another once it finishes.
+    fn start(&mut self) -> bool {
+        if self.in_flight {
+            self.outdated = true;
+            false
+        } else {
+            self.in_flight = true;
+            true
+        }
+    }
+
+    /// Ends the running write, telling whether another has to follow it.
+    fn finish(&mut self) -> bool {
+        self.in_flight = false;
+        std::mem::take(&mut self.outdated)
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.in_flight
+    }
+}
+
+/// An action that would throw away unsaved changes and is waiting on the
+/// user to decide what to do with them.
+#[derive(Debug, Clone, Copy)]
+enum PendingAction {
+    CloseTab(usize),
+    /// Closing the window, which is held up by this buffer.
+    Quit(usize),
+}
+
+impl PendingAction {
+    /// The buffer whose unsaved changes are at stake.
+    fn buffer(self) -> usize {
+        match self {
+            PendingAction::CloseTab(index) | PendingAction::Quit(index) => index,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Choice {
     Save,
-    FileSaved(Result<PathBuf, Error>),
-    ThemeSelected(highlighter::Theme),
+    Discard,
+    Cancel,
 }
 
 impl Application for Editor {
     type Message = Message;
     type Theme = Theme;
     type Executor = executor::Default;
-    type Flags = ();
+    type Flags = (Option<PathBuf>, Session);
+
+    fn new((path, session): Self::Flags) -> (Self, Command<Message>) {
+        let (config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(error) => (Config::default(), Some(error)),
+        };
+        let is_dark = config.dark_mode.unwrap_or(true);
+        let highlighter_theme = highlighter::Theme::ALL
+            .iter()
+            .copied()
+            .find(|theme| config.highlighter_theme.as_deref() == Some(&theme.to_string()))
+            .unwrap_or_else(|| highlighter_theme(is_dark));
+        let font_size = config
+            .font_size
+            .unwrap_or(DEFAULT_FONT_SIZE)
+            .clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        // The configured family is used right away and dropped if it turns
+        // out not to be installed.
+        let font_family = config
+            .font_family
+            .as_deref()
+            .map_or(font::DEFAULT, font::leak);
+        let restore_session = config.restore_session.unwrap_or(true);
+
+        // Without a file to open, the files that were open last time are
+        // opened again one after the other. The one that was active comes
+        // last, so it ends up active again.
+        let mut restored = VecDeque::new();
+
+        if path.is_none() && restore_session {
+            restored.extend(
+                session
+                    .open_files
+                    .iter()
+                    .chain(&session.last_file)
+                    .filter(|path| path.exists())
+                    .cloned(),
+            );
+        }
+
+        let path = path.or_else(|| restored.pop_front());
+
+        let command = match &path {
+            Some(path) => Command::perform(load_file(path.clone()), Message::FileOpened),
+            None => Command::none(),
+        };
+        let command = Command::batch([
+            command,
+            Command::perform(font::installed(), Message::FontsLoaded),
+        ]);
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Message>) {
         (
             Editor {
-                content: text_editor::Content::new(),
+                buffers: vec![Buffer::new()],
+                active: 0,
+                pending: None,
+                confirming: None,
+                discarded: Vec::new(),
+                dropped: restored,
+                is_hovering: false,
+                search: None,
+                go_to_line: None,
+                line_prefix: None,
+                palette: None,
                 error: None,
-                path: None,
-                theme: highlighter::Theme::SolarizedDark,
-                is_dirty: true,
+                notice: config_error,
+                status_message: None,
+                theme: app_theme(is_dark),
+                highlighter_theme,
+                font_size,
+                font_family,
+                font_families: if font_family == font::DEFAULT {
+                    vec![font::DEFAULT]
+                } else {
+                    vec![font::DEFAULT, font_family]
+                },
+                show_preferences: false,
+                font_size_input: None,
+                indent: Indent {
+                    use_spaces: config.use_spaces.unwrap_or(true),
+                    width: config
+                        .tab_width
+                        .filter(|width| indent::WIDTHS.contains(width))
+                        .unwrap_or(indent::DEFAULT_WIDTH),
+                },
+                auto_indent: config.auto_indent.unwrap_or(true),
+                autosave: config.autosave,
+                autosave_interval: config
+                    .autosave_interval
+                    .filter(|interval| AUTOSAVE_INTERVALS.contains(interval))
+                    .unwrap_or(DEFAULT_AUTOSAVE_INTERVAL),
+                backup: config.backup,
+                trim_whitespace: config.trim_whitespace,
+                final_newline: config.final_newline.unwrap_or(true),
+                restore_session,
+                timestamp_format: config
+                    .timestamp_format
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_TIMESTAMP_FORMAT.to_owned()),
+                modifiers: keyboard::Modifiers::default(),
+                config,
+                config_writes: Writes::default(),
+                session,
+                session_writes: Writes::default(),
+                is_closing: false,
+                keys_to_buffer: false,
+                show_welcome: path.is_none(),
+                is_loading: path.is_some(),
             },
-            Command::perform(load_file(default_file()), Message::FileOpened),
+            command,
         )
     }
 
     fn title(&self) -> String {
-        String::from("Rio Editor")
+        let buffer = self.buffer();
+        let name = buffer.name();
+
+        if buffer.is_dirty {
+            format!("{name}* - Rio Editor")
+        } else {
+            format!("{name} - Rio Editor")
+        }
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Message> {
         match message {
+            // The palette is modal, so the arrow keys that move through it
+            // do not also move the cursor behind it.
+            Message::Edit(_) if self.palette.is_some() => Command::none(),
             Message::Edit(action) => {
-                self.is_dirty = self.is_dirty || action.is_edit();
+                let indent = self.indent();
+                let action = match action {
+                    // The editor moves the cursor on Alt+Up and Alt+Down, so
+                    // those are caught here rather than as shortcuts.
+                    text_editor::Action::Move(text_editor::Motion::Up) if self.modifiers.alt() => {
+                        return self.update(Message::MoveLinesUp);
+                    }
+                    text_editor::Action::Move(text_editor::Motion::Down)
+                        if self.modifiers.alt() =>
+                    {
+                        return self.update(Message::MoveLinesDown);
+                    }
+                    text_editor::Action::Edit(text_editor::Edit::Insert('\t'))
+                        if self.modifiers.shift() =>
+                    {
+                        self.buffer_mut().dedent(indent);
+                        self.refresh_search();
+                        return Command::none();
+                    }
+                    text_editor::Action::Edit(text_editor::Edit::Insert('\t'))
+                        if self
+                            .buffer()
+                            .content
+                            .selection()
+                            .is_some_and(|selection| selection.contains('\n')) =>
+                    {
+                        self.buffer_mut().indent_lines(indent);
+                        self.refresh_search();
+                        return Command::none();
+                    }
+                    text_editor::Action::Edit(text_editor::Edit::Insert('\t'))
+                        if indent.use_spaces =>
+                    {
+                        let spaces = self.buffer().soft_tab(indent.width);
+
+                        text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(spaces)))
+                    }
+                    // Pasted text arrives as a single edit rather than as
+                    // separate line breaks, so it is never re-indented.
+                    text_editor::Action::Edit(text_editor::Edit::Enter) if self.auto_indent => {
+                        let newline = self.buffer().indented_newline(indent);
+
+                        text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(newline)))
+                    }
+                    action => action,
+                };
+                let is_edit = action.is_edit();
+
+                // Clicking the editor gives it the keyboard again.
+                if let text_editor::Action::Click(_) = action {
+                    self.keys_to_buffer = false;
+                }
+
+                self.show_welcome = false;
                 self.error = None;
-                self.content.edit(action);
+                self.notice = None;
+                self.buffer_mut().edit(action);
+
+                if is_edit {
+                    self.refresh_search();
+                }
+
                 Command::none()
             }
 
-            Message::New => {
-                self.path = None;
-                self.is_dirty = true;
-                self.content = text_editor::Content::new();
+            Message::Undo => {
+                self.buffer_mut().undo();
+                self.refresh_search();
+                Command::none()
+            }
+            Message::Redo => {
+                self.buffer_mut().redo();
+                self.refresh_search();
                 Command::none()
             }
 
-            Message::Open => Command::perform(pick_file(), Message::FileOpened),
-            Message::FileOpened(Ok((path, content))) => {
-                self.path = Some(path);
-                self.is_dirty = false;
-                self.content = text_editor::Content::with(&content);
+            Message::ToggleFind => {
+                if self.search.take().is_some() {
+                    return self.return_keyboard();
+                }
+
+                let content = &self.buffer().content;
+
+                // Searching for the selected word is the common case, so the
+                // selection becomes the query when it fits on one line.
+                let search = match content.selection() {
+                    Some(selection) if !selection.contains('\n') => Search::with_query(
+                        selection,
+                        &buffer::raw_text(content),
+                        content.cursor_position(),
+                    ),
+                    _ => Search::default(),
+                };
+
+                self.search = Some(search);
+                text_input::focus(find_input())
+            }
+            Message::FindQueryChanged(query) => {
+                let buffer = &mut self.buffers[self.active];
+
+                if let Some(search) = &mut self.search {
+                    let text = buffer::raw_text(&buffer.content);
+                    let position = buffer.content.cursor_position();
+
+                    if let Some(found) = search.set_query(query, &text, position) {
+                        buffer.select(found.line, found.start, found.end);
+                    }
+                }
+
                 Command::none()
             }
-            Message::FileOpened(Err(error)) => {
-                self.error = Some(error);
+            Message::FindNext => {
+                self.find(Search::next);
+                Command::none()
+            }
+            Message::FindPrevious => {
+                self.find(Search::previous);
                 Command::none()
             }
+            Message::RegexToggled(is_regex) => {
+                let text = buffer::raw_text(&self.buffer().content);
 
-            Message::Save => {
-                let text = self.content.text();
-                Command::perform(save_file(self.path.clone(), text), Message::FileSaved)
+                if let Some(search) = &mut self.search {
+                    search.is_regex = is_regex;
+                    search.refresh(&text);
+                }
+
+                Command::none()
             }
-            Message::FileSaved(Ok(path)) => {
-                self.path = Some(path);
-                self.is_dirty = false;
+            Message::ReplacementChanged(replacement) => {
+                if let Some(search) = &mut self.search {
+                    search.replacement = replacement;
+                }
+
                 Command::none()
             }
-            Message::FileSaved(Err(error)) => {
-                self.error = Some(error);
+            Message::Replace | Message::ReplaceAll if self.buffer().read_only => {
+                self.flash("File is read-only");
                 Command::none()
             }
+            Message::Replace => {
+                let buffer = &mut self.buffers[self.active];
 
-            Message::ThemeSelected(theme) => {
-                self.theme = theme;
+                let Some(search) = &mut self.search else {
+                    return Command::none();
+                };
+
+                // The matches may have been found in a different text, like
+                // that of another tab, and replacing them would cut it at
+                // the wrong places.
+                let text = buffer::raw_text(&buffer.content);
+                search.refresh(&text);
+
+                // The first press only selects a match, so nothing is
+                // replaced that the user hasn't seen.
+                let Some(found) = search.selected(buffer.content.cursor_position()) else {
+                    self.find(Search::next);
+                    return Command::none();
+                };
+
+                let (text, length) = search.replace(&text, found);
+                let cursor = (found.line, found.start + length);
+
+                buffer.set_text(&text, cursor);
+                search.refresh(&text);
+
+                self.find(Search::next);
                 Command::none()
             }
-        }
-    }
+            Message::ReplaceAll => {
+                let buffer = &mut self.buffers[self.active];
 
-    fn subscription(&self) -> iced::Subscription<Self::Message> {
-        keyboard::on_key_press(|key_code, modifiers| match key_code {
-            keyboard::KeyCode::N if modifiers.command() => Some(Message::New),
-            keyboard::KeyCode::O if modifiers.command() => Some(Message::Open),
-            keyboard::KeyCode::S if modifiers.command() => Some(Message::Save),
-            _ => None,
-        })
-    }
+                let Some(search) = &mut self.search else {
+                    return Command::none();
+                };
 
-    fn view(&self) -> iced::Element<'_, Self::Message> {
-        let controls = row![
-            action(new_icon(), "New File", Some(Message::New)),
-            action(load_icon(), "Open File", Some(Message::Open)),
-            action(
-                save_icon(),
-                "Save File",
-                self.is_dirty.then_some(Message::Save)
-            ),
-            horizontal_space(Length::Fill),
-            pick_list(
-                highlighter::Theme::ALL,
-                Some(self.theme),
-                Message::ThemeSelected
-            )
-        ]
-        .spacing(10);
+                let text = buffer::raw_text(&buffer.content);
+                search.refresh(&text);
 
-        let input = text_editor(&self.content)
-            .on_edit(Message::Edit)
-            .highlight::<Highlighter>(
-                highlighter::Settings {
-                    theme: self.theme,
-                    extension: self
-                        .path
-                        .as_ref()
-                        .and_then(|path| path.extension()?.to_str())
-                        .unwrap_or("rs")
-                        .to_string(),
-                },
-                |highlight, _theme| highlight.to_format(),
-            );
+                let count = search.matches.len();
 
-        let status_bar = {
-            let status = if let Some(Error::IoError(error)) = self.error.as_ref() {
-                text(error.to_string())
-            } else {
-                match self.path.as_deref().and_then(Path::to_str) {
-                    Some(path) => text(path).size(15),
-                    None => text("New File"),
+                if count == 0 {
+                    return Command::none();
                 }
-            };
 
-            let position = {
-                let (line, col) = self.content.cursor_position();
-                text(format!("{}:{}", line + 1, col + 1))
-            };
+                let text = search.replace_all(&text);
+                let cursor = buffer.content.cursor_position();
 
-            row![status, horizontal_space(Length::Fill), position]
-        };
+                buffer.set_text(&text, cursor);
+                search.refresh(&text);
 
-        container(column![controls, input, status_bar].spacing(10))
-            .padding(20)
-            .into()
-    }
+                self.notice = Some(if count == 1 {
+                    String::from("Replaced 1 occurrence")
+                } else {
+                    format!("Replaced {count} occurrences")
+                });
 
-    fn theme(&self) -> Theme {
-        if self.theme.is_dark() {
-            Theme::Dark
-        } else {
-            Theme::Light
-        }
-    }
-}
+                Command::none()
+            }
+            Message::CloseFind => {
+                self.search = None;
+                Command::none()
+            }
 
-fn action<'a>(
-    content: Element<'a, Message>,
-    label: &str,
-    on_press: Option<Message>,
-) -> Element<'a, Message> {
-    let is_disabled = on_press.is_none();
+            Message::OpenGoToLine => {
+                self.go_to_line = Some(String::new());
+                text_input::focus(go_to_line_input())
+            }
+            Message::GoToLineChanged(input) => {
+                self.go_to_line = Some(input);
+                Command::none()
+            }
+            Message::SelectAll => {
+                let buffer = self.buffer_mut();
 
-    tooltip(
-        button(container(content).width(30).center_x())
-            .on_press_maybe(on_press)
-            .padding([5, 10])
-            .style(if is_disabled {
-                theme::Button::Secondary
-            } else {
-                theme::Button::Primary
-            }),
-        label,
-        tooltip::Position::FollowCursor,
-    )
-    .style(theme::Container::Box)
-    .into()
-}
+                buffer.edit(text_editor::Action::Move(
+                    text_editor::Motion::DocumentStart,
+                ));
+                buffer.edit(text_editor::Action::Select(
+                    text_editor::Motion::DocumentEnd,
+                ));
 
-fn new_icon<'a>() -> Element<'a, Message> {
-    icon('\u{E800}')
-}
+                Command::none()
+            }
 
-fn save_icon<'a>() -> Element<'a, Message> {
-    icon('\u{E801}')
-}
+            Message::GoToLine => {
+                let Some(input) = self.go_to_line.take() else {
+                    return Command::none();
+                };
 
-fn load_icon<'a>() -> Element<'a, Message> {
-    icon('\u{F115}')
-}
+                // Invalid input just closes the prompt without moving.
+                if let Some((line, column)) = cursor::parse_position(&input) {
+                    let content = &mut self.buffer_mut().content;
+                    let line = line.min(content.line_count().saturating_sub(1));
+                    let column = content
+                        .line(line)
+                        .map(|text| cursor::column_of(&text, column))
+                        .unwrap_or_default();
 
-fn icon<'a>(codepoint: char) -> Element<'a, Message> {
-    const ICON_FONT: Font = Font::with_name("rio-icons");
+                    cursor::move_to(content, line, column);
+                }
 
-    text(codepoint).font(ICON_FONT).into()
-}
+                self.return_keyboard()
+            }
 
-fn default_file() -> PathBuf {
-    PathBuf::from(format!("{}/src/main.rs", env!("CARGO_MANIFEST_DIR")))
-}
+            Message::OpenLinePrefix => {
+                self.line_prefix = Some(String::new());
+                text_input::focus(line_prefix_input())
+            }
+            Message::LinePrefixChanged(input) => {
+                self.line_prefix = Some(input);
+                Command::none()
+            }
+            Message::InsertLinePrefix => {
+                if let Some(prefix) = self.line_prefix.take() {
+                    self.buffer_mut().prefix_lines(&prefix);
+                    self.refresh_search();
+                }
 
-async fn pick_file() -> Result<(PathBuf, Arc<String>), Error> {
-    let handle = rfd::AsyncFileDialog::new()
-        .set_title("Choose a text file")
-        .pick_file()
-        .await
-        .ok_or(Error::DialogError)?;
+                self.return_keyboard()
+            }
 
-    load_file(handle.path().to_owned()).await
-}
+            Message::Escape if self.confirming.is_some() => {
+                self.confirming = None;
+                self.is_loading = false;
+                Command::none()
+            }
+            Message::Escape if self.palette.is_some() => {
+                self.palette = None;
+                self.return_keyboard()
+            }
+            Message::Escape if self.show_preferences => {
+                self.show_preferences = false;
+                self.font_size_input = None;
+                Command::none()
+            }
+            Message::Escape => {
+                self.go_to_line = None;
+                self.line_prefix = None;
+                self.search = None;
+                self.return_keyboard()
+            }
 
-async fn load_file(path: PathBuf) -> Result<(PathBuf, Arc<String>), Error> {
-    let content = tokio::fs::read_to_string(&path)
-        .await
-        .map(Arc::new)
-        .map_err(|error| error.kind())
-        .map_err(Error::IoError)?;
+            Message::TogglePalette => {
+                if self.palette.take().is_some() {
+                    return self.return_keyboard();
+                }
 
-    Ok((path, content))
-}
+                self.palette = Some(Palette::default());
+                text_input::focus(palette_input())
+            }
+            Message::PaletteQueryChanged(query) => {
+                if let Some(palette) = &mut self.palette {
+                    palette.set_query(query);
+                }
 
-async fn save_file(path: Option<PathBuf>, text: String) -> Result<PathBuf, Error> {
-    let path = if let Some(path) = path {
-        path
-    } else {
-        rfd::AsyncFileDialog::new()
-            .set_title("Choose a file name")
-            .save_file()
-            .await
-            .ok_or(Error::DialogError)
-            .map(|handle| handle.path().to_owned())?
-    };
+                Command::none()
+            }
+            Message::PaletteMoved(down) => {
+                if let Some(palette) = &mut self.palette {
+                    palette.move_selection(down);
+                }
 
-    tokio::fs::write(&path, text)
-        .await
-        .map_err(|error| Error::IoError(error.kind()))?;
+                Command::none()
+            }
+            Message::PaletteSubmitted => {
+                let selection = self.palette.as_ref().and_then(Palette::selection);
 
-    Ok(path)
-}
+                match selection {
+                    Some(entry) => self.update(Message::RunCommand(Box::new(entry.message))),
+                    None => Command::none(),
+                }
+            }
+            Message::RunCommand(message) => {
+                self.palette = None;
+                self.keys_to_buffer = true;
+                self.update(*message)
+            }
 
-#[derive(Debug, Clone)]
-enum Error {
-    DialogError,
-    IoError(io::ErrorKind),
+            Message::New if !self.is_loading => {
+                if std::mem::take(&mut self.show_welcome) {
+                    return Command::none();
+                }
+
+                self.buffers.push(Buffer::new());
+                self.active = self.buffers.len() - 1;
+                self.refresh_search();
+                Command::none()
+            }
+
+            Message::NewFromTemplate(kind) if !self.is_loading => {
+                Command::perform(template::load(kind), Message::TemplateLoaded)
+            }
+            Message::TemplateLoaded((kind, text)) => {
+                let buffer = Buffer::from_template(&text, kind.extension());
+
+                self.show_welcome = false;
+
+                if self.buffer().is_pristine() {
+                    *self.buffer_mut() = buffer;
+                } else {
+                    self.buffers.push(buffer);
+                    self.active = self.buffers.len() - 1;
+                }
+
+                self.refresh_search();
+                Command::none()
+            }
+
+            Message::Open if !self.is_loading => {
+                self.is_loading = true;
+
+                Command::perform(pick_file(), Message::FileOpened)
+            }
+            Message::OpenRecent(path) if !self.is_loading => {
+                if !path.exists() {
+                    self.notice = Some(format!("{} no longer exists", path.display()));
+                    self.session.remove_recent(&path);
+                    return self.save_session();
+                }
+
+                self.is_loading = true;
+
+                Command::perform(load_file(path), Message::FileOpened)
+            }
+            // The session is written when the window closes, rather than on
+            // every step of a drag.
+            Message::WindowResized(width, height) => {
+                self.session.window_size = Some((width, height));
+                Command::none()
+            }
+            Message::WindowMoved(x, y) => {
+                self.session.window_position = Some((x, y));
+                Command::none()
+            }
+            Message::FileHovered => {
+                self.is_hovering = true;
+                Command::none()
+            }
+            Message::FilesHoveredLeft => {
+                self.is_hovering = false;
+                Command::none()
+            }
+            Message::FileDropped(path) => {
+                self.is_hovering = false;
+                self.dropped.push_back(path);
+
+                if self.is_loading {
+                    Command::none()
+                } else {
+                    self.open_dropped()
+                }
+            }
+            Message::FileOpened(Ok(file)) => {
+                let is_open = self
+                    .buffers
+                    .iter()
+                    .any(|buffer| buffer.path.as_ref() == Some(&file.path));
+                let remembered = self
+                    .session
+                    .encodings
+                    .get(&file.path)
+                    .and_then(|name| TextEncoding::from_name(name))
+                    .filter(|encoding| !is_open && *encoding != file.encoding);
+
+                // Read again in the encoding the user picked for this file
+                // last time, which detection got wrong then too.
+                if let Some(encoding) = remembered {
+                    return Command::perform(
+                        load_file_as(file.path, encoding),
+                        Message::FileOpened,
+                    );
+                }
+
+                self.is_loading = false;
+                self.show_welcome = false;
+                self.session.push_recent(file.path.clone());
+
+                if let Some(index) = self
+                    .buffers
+                    .iter()
+                    .position(|buffer| buffer.path.as_ref() == Some(&file.path))
+                {
+                    self.active = index;
+                } else {
+                    let position = self.session.cursors.get(&file.path).copied();
+                    let mut buffer = Buffer::open(&file);
+
+                    if let Some((line, column)) = position {
+                        cursor::move_to(&mut buffer.content, line, column);
+                    }
+
+                    if self.buffer().is_pristine() {
+                        *self.buffer_mut() = buffer;
+                    } else {
+                        self.buffers.push(buffer);
+                        self.active = self.buffers.len() - 1;
+                    }
+                }
+
+                self.refresh_search();
+                Command::batch([self.save_session(), self.open_dropped()])
+            }
+            Message::FileOpened(Err(Error::EncodingError(path, encoding))) => {
+                let name = path.file_name().map_or_else(
+                    || path.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                );
+
+                self.error = Some(Error::EncodingError(path.clone(), encoding));
+
+                Command::perform(confirm_lossy_open(name, encoding), move |confirmed| {
+                    Message::LossyOpenConfirmed(path.clone(), confirmed)
+                })
+            }
+            Message::LossyOpenConfirmed(path, true) => {
+                self.error = None;
+
+                Command::perform(load_file_lossy(path), Message::FileOpened)
+            }
+            Message::LossyOpenConfirmed(_, false) => {
+                self.is_loading = false;
+                self.open_dropped()
+            }
+            // Cancelling the dialog isn't an error worth showing.
+            Message::FileOpened(Err(Error::DialogError)) => {
+                self.is_loading = false;
+                self.open_dropped()
+            }
+            Message::FileOpened(Err(error)) => {
+                self.is_loading = false;
+                self.error = Some(error);
+                self.open_dropped()
+            }
+
+            Message::Reload if !self.is_loading => self.reload(None),
+            Message::Revert if !self.is_loading && self.buffer().is_dirty => self.reload(None),
+            Message::ReopenWithEncoding(encoding) if !self.is_loading => {
+                if let Some(path) = self.buffer().path.clone() {
+                    self.session.encodings.insert(path, encoding.to_string());
+                }
+
+                Command::batch([self.reload(Some(encoding)), self.save_session()])
+            }
+            Message::ReloadConfirmed(index, encoding, true) => {
+                match self
+                    .buffers
+                    .get(index)
+                    .and_then(|buffer| buffer.path.clone())
+                {
+                    Some(path) => Command::perform(load_file_as(path, encoding), move |result| {
+                        Message::FileReloaded(index, result)
+                    }),
+                    None => {
+                        self.is_loading = false;
+                        Command::none()
+                    }
+                }
+            }
+            Message::ReloadConfirmed(_, _, false) => {
+                self.is_loading = false;
+                Command::none()
+            }
+            Message::FileReloaded(index, Ok(file)) => {
+                self.is_loading = false;
+
+                if let Some(buffer) = self.buffers.get_mut(index) {
+                    buffer.reload(&file);
+                }
+
+                self.refresh_search();
+                Command::none()
+            }
+            Message::FileReloaded(_, Err(error)) => {
+                self.is_loading = false;
+                self.error = Some(error);
+                Command::none()
+            }
+
+            Message::Save if !self.is_loading => self.save(self.active, false),
+            Message::SaveAs if !self.is_loading => self.save(self.active, true),
+            Message::OverwriteConfirmed(index, true) => {
+                self.is_loading = false;
+                self.write(index, false)
+            }
+            Message::OverwriteConfirmed(_, false) => {
+                self.is_loading = false;
+                self.pending = None;
+                Command::none()
+            }
+            Message::LineEndingsConfirmed(index, save_as, true) => {
+                self.is_loading = false;
+
+                match self.buffers.get_mut(index) {
+                    Some(buffer) => {
+                        buffer.mixed_line_endings = false;
+                        self.save(index, save_as)
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::LineEndingsConfirmed(_, _, false) => {
+                self.is_loading = false;
+                self.pending = None;
+                Command::none()
+            }
+            Message::FileSaved(
+                index,
+                Ok(SavedFile {
+                    path,
+                    stamp,
+                    fingerprint,
+                    backup_error,
+                }),
+            ) => {
+                self.is_loading = false;
+
+                match backup_error {
+                    Some(error) => {
+                        self.notice = Some(format!("Saved, but the backup failed: {error}"));
+                    }
+                    None => self.flash("Saved"),
+                }
+
+                self.session.push_recent(path.clone());
+
+                if let Some(buffer) = self.buffers.get_mut(index) {
+                    // A picked encoding is kept up to date with the one the
+                    // file was written in, which it is read in again.
+                    if let Some(name) = self.session.encodings.get_mut(&path) {
+                        *name = buffer.encoding.to_string();
+                    }
+
+                    buffer.mark_saved(path, stamp, fingerprint);
+                }
+
+                let pending = match self.pending.take() {
+                    Some(pending) => self.perform(pending),
+                    None => Command::none(),
+                };
+
+                Command::batch([pending, self.save_session()])
+            }
+            Message::FileSaved(
+                index,
+                Err(error @ Error::FileError(_, _, io::ErrorKind::PermissionDenied, _)),
+            ) => {
+                let name = self.buffers.get(index).map_or("New File", Buffer::name);
+                let ask = confirm_save_elsewhere(name.to_owned());
+
+                self.error = Some(error);
+
+                Command::perform(ask, move |confirmed| {
+                    Message::SaveElsewhereConfirmed(index, confirmed)
+                })
+            }
+            Message::SaveElsewhereConfirmed(index, true) => {
+                self.is_loading = false;
+                self.error = None;
+                self.write(index, true)
+            }
+            Message::SaveElsewhereConfirmed(_, false) => {
+                self.is_loading = false;
+                self.pending = None;
+                Command::none()
+            }
+            Message::FileSaved(_, Err(Error::DialogError)) => {
+                self.is_loading = false;
+                self.pending = None;
+                Command::none()
+            }
+            Message::FileSaved(_, Err(error)) => {
+                self.is_loading = false;
+                self.pending = None;
+                self.error = Some(error);
+                Command::none()
+            }
+            Message::Autosaved(index, Ok(saved)) => {
+                self.update(Message::FileSaved(index, Ok(saved)))
+            }
+            Message::Autosaved(index, Err(error)) => {
+                self.is_loading = false;
+
+                // Autosaves skip a file they aren't allowed to write, and
+                // saving it by hand offers to save it elsewhere.
+                if let Error::FileError(_, _, io::ErrorKind::PermissionDenied, _) = error {
+                    if let Some(buffer) = self.buffers.get_mut(index) {
+                        buffer.is_writable = false;
+                    }
+                }
+
+                self.error = Some(error);
+                Command::none()
+            }
+
+            Message::FileChangedExternally(paths) => {
+                Command::perform(stamp_files(paths), Message::DiskChecked)
+            }
+            Message::DiskChecked(stamps) => {
+                // A save may have started since the files were checked, in
+                // which case the new stamps could be our own write.
+                if self.is_loading {
+                    return Command::none();
+                }
+
+                for (path, stamp) in stamps {
+                    for buffer in &mut self.buffers {
+                        if buffer.path.as_ref() == Some(&path)
+                            && buffer.stamp.is_some()
+                            && buffer.stamp != stamp
+                        {
+                            buffer.stamp = stamp;
+                            buffer.changed_on_disk = true;
+                            buffer.keep_changes = false;
+                        }
+                    }
+                }
+
+                Command::none()
+            }
+            Message::KeepChanges => {
+                self.buffer_mut().keep_changes = true;
+                Command::none()
+            }
+
+            Message::TabSelected(index) => {
+                self.active = index.min(self.buffers.len() - 1);
+                self.refresh_search();
+                self.save_session()
+            }
+            Message::TabClosed(index) if !self.is_loading => {
+                let Some(buffer) = self.buffers.get(index) else {
+                    return Command::none();
+                };
+
+                if buffer.is_dirty && !buffer.is_pristine() {
+                    self.confirming = Some(PendingAction::CloseTab(index));
+                    self.is_loading = true;
+
+                    return Command::none();
+                }
+
+                self.perform(PendingAction::CloseTab(index))
+            }
+            Message::Close => self.update(Message::TabClosed(self.active)),
+            Message::CopyPath => match &self.buffer().path {
+                Some(path) => {
+                    let path = path.to_string_lossy().into_owned();
+                    self.flash("Copied to clipboard");
+
+                    clipboard::write(path)
+                }
+                None => {
+                    self.flash("No file path");
+                    Command::none()
+                }
+            },
+            Message::RevealInFolder => match self.buffer().path.clone() {
+                Some(path) => Command::perform(reveal_in_folder(path), Message::Revealed),
+                None => Command::none(),
+            },
+            Message::Revealed(Ok(())) => Command::none(),
+            Message::Revealed(Err(error)) => {
+                self.error = Some(error);
+                Command::none()
+            }
+            Message::MoveLinesUp => {
+                self.buffer_mut().move_lines(false);
+                self.refresh_search();
+                Command::none()
+            }
+            Message::MoveLinesDown => {
+                self.buffer_mut().move_lines(true);
+                self.refresh_search();
+                Command::none()
+            }
+            Message::DeleteLines => {
+                self.buffer_mut().delete_lines();
+                self.refresh_search();
+                Command::none()
+            }
+            Message::JoinLines => {
+                self.buffer_mut().join_lines();
+                self.refresh_search();
+                Command::none()
+            }
+            Message::ChangeCase(case) => {
+                self.buffer_mut().change_case(case);
+                self.refresh_search();
+                Command::none()
+            }
+            Message::SortLines(sort) => {
+                self.buffer_mut().sort_lines(sort);
+                self.refresh_search();
+                Command::none()
+            }
+            Message::Duplicate => {
+                self.buffer_mut().duplicate();
+                self.refresh_search();
+                Command::none()
+            }
+            Message::ToggleComment => {
+                let syntax = self.buffer().syntax();
+
+                match language::line_comment(syntax) {
+                    Some(marker) => {
+                        self.buffer_mut().toggle_comment(marker);
+                        self.refresh_search();
+                    }
+                    None => {
+                        let name = language::name(syntax);
+                        self.flash(format!("{name} has no line comments"));
+                    }
+                }
+
+                Command::none()
+            }
+            Message::ToggleReadOnly => {
+                let buffer = self.buffer_mut();
+                buffer.read_only = !buffer.read_only;
+                Command::none()
+            }
+            Message::ConfirmDiscard(pending, choice) => {
+                self.confirming = None;
+                self.is_loading = false;
+
+                match choice {
+                    Choice::Save => {
+                        self.pending = Some(pending);
+                        self.save(pending.buffer(), false)
+                    }
+                    Choice::Discard => self.perform(pending),
+                    Choice::Cancel => Command::none(),
+                }
+            }
+
+            Message::SetLanguage(language) => {
+                self.buffer_mut().language = language;
+                Command::none()
+            }
+            Message::IndentSelected(indent) => {
+                self.buffer_mut().indent = Some(indent);
+                Command::none()
+            }
+            Message::NormalizeIndent => {
+                let indent = self.indent();
+                self.buffer_mut().normalize_indent(indent);
+                self.refresh_search();
+                Command::none()
+            }
+
+            Message::SelectHighlightTheme(theme) => {
+                self.theme = app_theme(theme.is_dark());
+                self.highlighter_theme = theme;
+                self.config.dark_mode = Some(theme.is_dark());
+                self.config.highlighter_theme = Some(theme.to_string());
+                self.save_config()
+            }
+            Message::ToggleTheme => {
+                let other_theme = app_theme(!matches!(self.theme, Theme::Dark));
+
+                self.update(Message::ThemeChanged(other_theme))
+            }
+            Message::ThemeChanged(theme) => {
+                let is_dark = matches!(theme, Theme::Dark);
+
+                // Keep the user's highlighter theme if it already suits the
+                // new background, otherwise switch to one that does.
+                if self.highlighter_theme.is_dark() != is_dark {
+                    self.highlighter_theme = highlighter_theme(is_dark);
+                    self.config.highlighter_theme = Some(self.highlighter_theme.to_string());
+                }
+
+                self.theme = theme;
+                self.config.dark_mode = Some(is_dark);
+                self.save_config()
+            }
+
+            Message::ConvertLineEndings(line_ending) => {
+                self.buffer_mut().set_line_ending(line_ending);
+                Command::none()
+            }
+            Message::EncodingSelected(encoding) => {
+                self.buffer_mut().set_encoding(encoding);
+                Command::none()
+            }
+            Message::TogglePreferences => {
+                self.show_preferences = !self.show_preferences;
+                self.font_size_input = None;
+                Command::none()
+            }
+            Message::FontSizeInputChanged(input) => {
+                // Sizes out of range are kept as typed, and only shown as
+                // such, so they can be corrected.
+                let font_size = input
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|size| (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(size));
+
+                self.font_size_input = Some(input);
+
+                match font_size {
+                    Some(font_size) => {
+                        self.font_size = font_size;
+                        self.config.font_size = Some(font_size);
+                        self.save_config()
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::UseSpacesToggled(use_spaces) => {
+                self.indent.use_spaces = use_spaces;
+                self.config.use_spaces = Some(use_spaces);
+                self.save_config()
+            }
+            Message::TabWidthSelected(tab_width) => {
+                self.indent.width = tab_width;
+                self.config.tab_width = Some(tab_width);
+                self.save_config()
+            }
+            Message::AutoIndentToggled(auto_indent) => {
+                self.auto_indent = auto_indent;
+                self.config.auto_indent = Some(auto_indent);
+                self.save_config()
+            }
+            Message::AutosaveToggled(autosave) => {
+                self.autosave = autosave;
+                self.config.autosave = autosave;
+                self.save_config()
+            }
+            Message::AutosaveIntervalSelected(interval) => {
+                self.autosave_interval = interval;
+                self.config.autosave_interval = Some(interval);
+                self.save_config()
+            }
+            Message::BackupToggled(backup) => {
+                self.backup = backup;
+                self.config.backup = backup;
+                self.save_config()
+            }
+            Message::TrimWhitespaceToggled(trim_whitespace) => {
+                self.trim_whitespace = trim_whitespace;
+                self.config.trim_whitespace = trim_whitespace;
+                self.save_config()
+            }
+            Message::FinalNewlineToggled(final_newline) => {
+                self.final_newline = final_newline;
+                self.config.final_newline = Some(final_newline);
+                self.save_config()
+            }
+            Message::TimestampFormatChanged(format) => {
+                self.config.timestamp_format = Some(format.clone());
+                self.timestamp_format = format;
+                self.save_config()
+            }
+            Message::InsertTimestamp => {
+                // A format that is still being typed would make chrono fail,
+                // so the default is used until it is valid.
+                let format = if is_valid_timestamp_format(&self.timestamp_format) {
+                    self.timestamp_format.as_str()
+                } else {
+                    DEFAULT_TIMESTAMP_FORMAT
+                };
+                let timestamp = chrono::Local::now().format(format).to_string();
+
+                self.update(Message::Edit(text_editor::Action::Edit(
+                    text_editor::Edit::Paste(Arc::new(timestamp)),
+                )))
+            }
+            Message::RestoreSessionToggled(restore_session) => {
+                self.restore_session = restore_session;
+                self.config.restore_session = Some(restore_session);
+                self.save_config()
+            }
+            Message::Autosave if !self.is_loading => {
+                // Untitled buffers are left alone, since saving them would
+                // pop up a dialog out of nowhere.
+                match self.buffers.iter().position(|buffer| {
+                    buffer.is_dirty
+                        && buffer.path.is_some()
+                        && buffer.is_writable
+                        && !buffer.mixed_line_endings
+                        && !buffer.changed_on_disk
+                }) {
+                    // Saved as is, since trimming could eat a space that was
+                    // just typed.
+                    Some(index) => self.write(index, false).map(|message| match message {
+                        Message::FileSaved(index, result) => Message::Autosaved(index, result),
+                        message => message,
+                    }),
+                    None => Command::none(),
+                }
+            }
+            Message::FontsLoaded(installed) => {
+                // The configured family is offered even if it doesn't say
+                // it is monospace.
+                let extra: Vec<String> = self
+                    .config
+                    .font_families
+                    .iter()
+                    .chain(&self.config.font_family)
+                    .cloned()
+                    .collect();
+                self.font_families = font::families(&installed, &extra);
+
+                if !installed.contains(self.font_family) {
+                    self.notice = Some(format!(
+                        "Font {} is not installed, using {}",
+                        self.font_family,
+                        font::DEFAULT
+                    ));
+                    self.font_family = font::DEFAULT;
+                }
+
+                Command::none()
+            }
+            Message::FontSelected(family) => {
+                self.font_family = family;
+                self.config.font_family = Some(family.to_owned());
+                self.save_config()
+            }
+            Message::ZoomIn => self.zoom(self.font_size + 1.0),
+            Message::ZoomOut => self.zoom(self.font_size - 1.0),
+            Message::ZoomReset => self.zoom(DEFAULT_FONT_SIZE),
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+                Command::none()
+            }
+            Message::WheelScrolled(delta) if self.modifiers.command() => {
+                let (mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. }) =
+                    delta;
+
+                if y > 0.0 {
+                    self.zoom(self.font_size + 1.0)
+                } else if y < 0.0 {
+                    self.zoom(self.font_size - 1.0)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::WheelScrolled(_) => Command::none(),
+            Message::ClearStatus => {
+                if self
+                    .status_message
+                    .as_ref()
+                    .is_some_and(|(_, shown)| shown.elapsed() >= STATUS_DURATION)
+                {
+                    self.status_message = None;
+                }
+
+                Command::none()
+            }
+
+            Message::RefreshFileInfo => Command::none(),
+            Message::DismissError => {
+                self.error = None;
+                Command::none()
+            }
+            Message::RefreshStats => {
+                for buffer in &mut self.buffers {
+                    buffer.refresh_stats();
+                }
+
+                Command::none()
+            }
+
+            Message::CloseRequested if !self.is_loading => {
+                self.discarded.clear();
+                self.close_window()
+            }
+            Message::CloseRequested => Command::none(),
+
+            Message::ConfigSaved(result) => {
+                if let Err(Error::IoError(kind)) = result {
+                    self.notice = Some(format!(
+                        "Couldn't save the settings: {}",
+                        io_error_message(kind)
+                    ));
+                }
+
+                if self.config_writes.finish() {
+                    self.save_config()
+                } else {
+                    self.close_if_done()
+                }
+            }
+            Message::SessionSaved(_) => {
+                if self.session_writes.finish() {
+                    self.save_session()
+                } else {
+                    self.close_if_done()
+                }
+            }
+
+            Message::New
+            | Message::NewFromTemplate(_)
+            | Message::Open
+            | Message::OpenRecent(_)
+            | Message::Reload
+            | Message::Revert
+            | Message::ReopenWithEncoding(_)
+            | Message::Save
+            | Message::SaveAs
+            | Message::TabClosed(_)
+            | Message::Autosave => Command::none(),
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        // Shortcuts and other window-wide events are handled even when a
+        // widget captured them, so they keep working while typing in the
+        // find bar. A focused text input captures every key, though, so
+        // shortcuts that edit the buffer are left to it.
+        let shortcuts = subscription::events_with(|event, status| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            }) => shortcut(key_code, modifiers).or_else(|| match status {
+                event::Status::Ignored => editing_shortcut(key_code, modifiers),
+                event::Status::Captured => None,
+            }),
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                Some(Message::ModifiersChanged(modifiers))
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                Some(Message::WheelScrolled(delta))
+            }
+            Event::Window(window::Event::Resized { width, height }) => {
+                Some(Message::WindowResized(width, height))
+            }
+            Event::Window(window::Event::Moved { x, y }) => Some(Message::WindowMoved(x, y)),
+            Event::Window(window::Event::FileHovered(_)) => Some(Message::FileHovered),
+            Event::Window(window::Event::FilesHoveredLeft) => Some(Message::FilesHoveredLeft),
+            Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+            Event::Window(window::Event::CloseRequested) => Some(Message::CloseRequested),
+            _ => None,
+        });
+
+        // The welcome screen has no text editor to type into, and the editor
+        // doesn't get the keyboard back when a prompt closes, so keys no
+        // widget took are forwarded to the buffer. The palette moves its
+        // selection with the arrow keys instead.
+        let typing = if self.show_welcome || (self.keys_to_buffer && self.palette.is_none()) {
+            subscription::events_with(|event, status| match (event, status) {
+                (
+                    Event::Keyboard(keyboard::Event::CharacterReceived(c)),
+                    event::Status::Ignored,
+                ) if !c.is_control() => Some(Message::Edit(text_editor::Action::Edit(
+                    text_editor::Edit::Insert(c),
+                ))),
+                (
+                    Event::Keyboard(keyboard::Event::KeyPressed {
+                        key_code,
+                        modifiers,
+                    }),
+                    event::Status::Ignored,
+                ) => editor_key(key_code, modifiers).map(Message::Edit),
+                _ => None,
+            })
+        } else {
+            Subscription::none()
+        };
+
+        // Counting is linear in the size of the buffer, so it is done at
+        // most twice a second rather than on every keystroke.
+        let stats = if self.buffers.iter().any(|buffer| buffer.stats_outdated) {
+            time::every(Duration::from_millis(500)).map(|_| Message::RefreshStats)
+        } else {
+            Subscription::none()
+        };
+
+        let autosave = if self.autosave {
+            time::every(Duration::from_secs(self.autosave_interval)).map(|_| Message::Autosave)
+        } else {
+            Subscription::none()
+        };
+
+        let paths: Vec<PathBuf> = self
+            .buffers
+            .iter()
+            .filter_map(|buffer| buffer.path.clone())
+            .collect();
+
+        let disk = if paths.is_empty() {
+            Subscription::none()
+        } else {
+            subscription::channel(paths.clone(), 100, move |output| watch_files(paths, output))
+        };
+
+        // How long ago the file was modified is shown in minutes, so it only
+        // needs refreshing now and then.
+        let file_info = if self.buffer().stamp.is_some() {
+            time::every(Duration::from_secs(30)).map(|_| Message::RefreshFileInfo)
+        } else {
+            Subscription::none()
+        };
+
+        let status = if self.status_message.is_some() {
+            time::every(Duration::from_millis(250)).map(|_| Message::ClearStatus)
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch([shortcuts, typing, stats, autosave, disk, file_info, status])
+    }
+
+    fn view(&self) -> iced::Element<'_, Self::Message> {
+        if let Some(pending) = self.confirming {
+            return self.confirm_discard(pending);
+        }
+
+        let buffer = self.buffer();
+
+        let controls = row![
+            action(
+                new_icon(),
+                "New File (Ctrl+N)",
+                (!self.is_loading).then_some(Message::New)
+            ),
+            action(
+                load_icon(),
+                "Open File (Ctrl+O)",
+                (!self.is_loading).then_some(Message::Open)
+            ),
+            pick_list(
+                self.session
+                    .recent_files
+                    .iter()
+                    .filter(|path| path.exists())
+                    .cloned()
+                    .map(RecentFile)
+                    .collect::<Vec<_>>(),
+                None,
+                |recent: RecentFile| Message::OpenRecent(recent.0)
+            )
+            .placeholder("Recent files")
+            .width(200),
+            pick_list(&TemplateKind::ALL[..], None, Message::NewFromTemplate)
+                .placeholder("New from template"),
+            action(
+                save_icon(),
+                "Save File (Ctrl+S)",
+                (buffer.is_dirty && !self.is_loading).then_some(Message::Save)
+            ),
+            action(
+                save_as_icon(),
+                "Save File As (Ctrl+Shift+S)",
+                (!self.is_loading).then_some(Message::SaveAs)
+            ),
+            action(
+                undo_icon(),
+                "Undo (Ctrl+Z)",
+                buffer.can_undo().then_some(Message::Undo)
+            ),
+            action(
+                redo_icon(),
+                "Redo (Ctrl+Y)",
+                buffer.can_redo().then_some(Message::Redo)
+            ),
+            action(
+                copy_path_icon(),
+                "Copy File Path (Ctrl+Shift+C)",
+                Some(Message::CopyPath)
+            ),
+            action(
+                reveal_icon(),
+                "Open Containing Folder",
+                buffer.path.is_some().then_some(Message::RevealInFolder)
+            ),
+            action(
+                reload_icon(),
+                "Reload From Disk (F5)",
+                (buffer.path.is_some() && !self.is_loading).then_some(Message::Reload)
+            ),
+            action(
+                revert_icon(),
+                "Revert to Saved (Ctrl+R)",
+                (buffer.path.is_some() && buffer.is_dirty && !self.is_loading)
+                    .then_some(Message::Revert)
+            ),
+            action(
+                lock_icon(buffer.read_only),
+                if buffer.read_only {
+                    "Allow Editing"
+                } else {
+                    "Make Read-Only"
+                },
+                Some(Message::ToggleReadOnly)
+            ),
+            horizontal_space(Length::Fill),
+            action(
+                preferences_icon(),
+                "Preferences",
+                Some(Message::TogglePreferences)
+            ),
+            action(
+                theme_icon(),
+                "Toggle Light/Dark Theme",
+                Some(Message::ToggleTheme)
+            ),
+            pick_list(
+                highlighter::Theme::ALL,
+                Some(self.highlighter_theme),
+                Message::SelectHighlightTheme
+            ),
+            pick_list(
+                &self.font_families[..],
+                Some(self.font_family),
+                Message::FontSelected
+            )
+        ]
+        .spacing(10);
+
+        let tabs = Row::with_children(
+            self.buffers
+                .iter()
+                .enumerate()
+                .map(|(index, tab)| tab_button(index, tab, index == self.active))
+                .collect(),
+        )
+        .spacing(5);
+
+        let input = text_editor(&buffer.content)
+            .font(font::font(self.font_family))
+            .size(self.font_size)
+            .on_edit(Message::Edit);
+
+        let input: Element<_> = match language::highlight_extension(buffer.syntax()) {
+            Some(extension) => input
+                .highlight::<Highlighter>(
+                    highlighter::Settings {
+                        theme: self.highlighter_theme,
+                        extension,
+                    },
+                    |highlight, _theme| highlight.to_format(),
+                )
+                .into(),
+            None => input.into(),
+        };
+
+        let status_bar = {
+            let status = if let Some(Error::IoError(error)) = self.error.as_ref() {
+                error_text(io_error_message(*error))
+            } else if let Some(Error::FileError(operation, path, _, message)) = self.error.as_ref()
+            {
+                error_text(format!(
+                    "Failed to {operation} {}: {message}",
+                    path.display()
+                ))
+            } else if let Some(Error::EncodingError(path, encoding)) = self.error.as_ref() {
+                error_text(format!("{} is not valid {encoding}", path.display()))
+            } else if let Some(Error::ReadOnlyFile(path)) = self.error.as_ref() {
+                error_text(format!("{} is read-only", path.display()))
+            } else if let Some(Error::BinaryFile(path)) = self.error.as_ref() {
+                error_text(format!("{} is binary and can't be opened", path.display()))
+            } else if let Some(Error::UnencodableCharacter(character, encoding)) =
+                self.error.as_ref()
+            {
+                error_text(format!("{character:?} can't be saved as {encoding}"))
+            } else if let Some((message, _)) = &self.status_message {
+                text(message).size(15)
+            } else if let Some(notice) = &self.notice {
+                text(notice).size(15)
+            } else if self.show_welcome {
+                text("No file").size(15)
+            } else {
+                let path = match buffer.path.as_deref().and_then(Path::to_str) {
+                    Some(path) => path,
+                    None => "New File",
+                };
+
+                if buffer.is_dirty {
+                    text(format!("* {path}")).size(15)
+                } else {
+                    text(path).size(15)
+                }
+            };
+
+            let file_info = text(buffer.stamp.map(file_info).unwrap_or_default()).size(15);
+
+            let stats = {
+                let stats = buffer.stats;
+
+                text(format!("{} words, {} chars", stats.words, stats.chars)).size(15)
+            };
+
+            let position = {
+                let (line, col) = buffer.content.cursor_position();
+                let mut position = match buffer.line_count {
+                    1 => format!("{}:{} / 1 line", line + 1, col + 1),
+                    count => format!("{}:{} / {count} lines", line + 1, col + 1),
+                };
+
+                if let Some(selection) = buffer.content.selection() {
+                    let selected = buffer::Stats::count(&selection);
+
+                    position.push_str(&match selected.lines {
+                        1 => format!(" ({} chars selected)", selected.chars),
+                        lines => format!(" ({} chars selected, {lines} lines)", selected.chars),
+                    });
+                }
+
+                button(text(position))
+                    .on_press(Message::OpenGoToLine)
+                    .padding(0)
+                    .style(theme::Button::Text)
+            };
+
+            let encoding = pick_list(
+                TextEncoding::choices(buffer.encoding),
+                Some(buffer.encoding),
+                Message::EncodingSelected,
+            )
+            .text_size(15);
+
+            let reopen = pick_list(
+                TextEncoding::choices(buffer.encoding),
+                None,
+                Message::ReopenWithEncoding,
+            )
+            .placeholder("Reopen with")
+            .text_size(15);
+
+            let line_ending = pick_list(
+                &LineEnding::ALL[..],
+                Some(buffer.line_ending),
+                Message::ConvertLineEndings,
+            )
+            .text_size(15);
+
+            let indent = pick_list(
+                Indent::choices(),
+                Some(self.indent()),
+                Message::IndentSelected,
+            )
+            .text_size(15);
+
+            let mixed_indent = buffer.mixed_indent.then(|| {
+                tooltip(
+                    button(text("Mixed indentation").size(15))
+                        .on_press_maybe((!buffer.read_only).then_some(Message::NormalizeIndent))
+                        .padding([2, 5])
+                        .style(theme::Button::Destructive),
+                    format!(
+                        "Tabs and spaces are both used. Click to convert to {}.",
+                        self.indent()
+                    ),
+                    tooltip::Position::Top,
+                )
+                .style(theme::Container::Box)
+            });
+
+            let language = pick_list(
+                Language::all(),
+                buffer.language.as_deref().and_then(|extension| {
+                    Language::all()
+                        .into_iter()
+                        .find(|language| language.extension == Some(extension))
+                }),
+                |language: Language| Message::SetLanguage(language.extension.map(str::to_owned)),
+            )
+            .placeholder(language::name(buffer.extension()))
+            .text_size(15);
+
+            let mixed_line_endings = buffer.mixed_line_endings.then(|| {
+                tooltip(
+                    button(text("Mixed line endings").size(15))
+                        .on_press_maybe(
+                            (!buffer.read_only)
+                                .then_some(Message::ConvertLineEndings(buffer.line_ending)),
+                        )
+                        .padding([2, 5])
+                        .style(theme::Button::Destructive),
+                    format!(
+                        "LF and CRLF are both used. Click to convert to {}.",
+                        buffer.line_ending
+                    ),
+                    tooltip::Position::Top,
+                )
+                .style(theme::Container::Box)
+            });
+
+            let mut status_bar = row![status].spacing(20);
+
+            if self.error.is_some() {
+                status_bar = status_bar.push(
+                    button(text("\u{2715}").size(13))
+                        .on_press(Message::DismissError)
+                        .padding([0, 5])
+                        .style(theme::Button::Text),
+                );
+            }
+
+            status_bar = status_bar.push(horizontal_space(Length::Fill));
+
+            if buffer.read_only {
+                status_bar = status_bar.push(
+                    row![lock_icon(true), text("Read-only").size(15)]
+                        .spacing(5)
+                        .align_items(Alignment::Center),
+                );
+            }
+
+            if let Some(mixed_indent) = mixed_indent {
+                status_bar = status_bar.push(mixed_indent);
+            }
+
+            if let Some(mixed_line_endings) = mixed_line_endings {
+                status_bar = status_bar.push(mixed_line_endings);
+            }
+
+            status_bar.push(
+                row![
+                    language,
+                    indent,
+                    encoding,
+                    reopen,
+                    line_ending,
+                    file_info,
+                    stats,
+                    position
+                ]
+                .spacing(20),
+            )
+        };
+
+        let body = if self.show_welcome {
+            self.welcome()
+        } else {
+            let mut body = column![tabs].spacing(10);
+
+            if buffer.changed_on_disk && !buffer.keep_changes {
+                body = body.push(
+                    row![
+                        text(format!("{} was changed by another program.", buffer.name())).size(15),
+                        horizontal_space(Length::Fill),
+                        button(text("Reload")).on_press(Message::Reload),
+                        button(text("Keep mine")).on_press(Message::KeepChanges),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                );
+            }
+
+            body.push(input).into()
+        };
+
+        let mut content = column![controls].spacing(10);
+
+        if let Some(palette) = &self.palette {
+            content = content.push(command_palette(palette));
+        }
+
+        // The preferences take the place of the editor while they are open.
+        content = if self.show_preferences {
+            content.push(container(self.preferences()).height(Length::Fill))
+        } else {
+            content.push(body)
+        };
+
+        if let Some(search) = &self.search {
+            content = content.push(find_bar(search));
+        }
+
+        if let Some(input) = &self.go_to_line {
+            content = content.push(
+                row![
+                    text("Go to").size(15),
+                    text_input("line or line:column", input)
+                        .id(go_to_line_input())
+                        .on_input(Message::GoToLineChanged)
+                        .on_submit(Message::GoToLine)
+                        .padding(5)
+                        .width(200),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+            );
+        }
+
+        if let Some(input) = &self.line_prefix {
+            content = content.push(
+                row![
+                    text("Insert at line starts").size(15),
+                    text_input("text", input)
+                        .id(line_prefix_input())
+                        .on_input(Message::LinePrefixChanged)
+                        .on_submit(Message::InsertLinePrefix)
+                        .padding(5)
+                        .width(200),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+            );
+        }
+
+        let content = container(content.push(status_bar)).padding(20);
+
+        if self.is_hovering {
+            container(content)
+                .style(theme::Container::Custom(Box::new(DropTarget)))
+                .into()
+        } else {
+            content.into()
+        }
+    }
+
+    fn theme(&self) -> Theme {
+        self.theme.clone()
+    }
+}
+
+impl Editor {
+    fn buffer(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+
+    fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
+
+    /// The indentation of the active buffer.
+    fn indent(&self) -> Indent {
+        self.buffer().indent.unwrap_or(self.indent)
+    }
+
+    fn preferences(&self) -> Element<'_, Message> {
+        let input = self
+            .font_size_input
+            .clone()
+            .unwrap_or_else(|| self.font_size.to_string());
+        let is_valid = input
+            .trim()
+            .parse::<f32>()
+            .is_ok_and(|size| (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&size));
+
+        let mut font_size = row![
+            text("Font size").size(15),
+            button(text("-").size(15))
+                .on_press(Message::ZoomOut)
+                .padding([0, 8]),
+            text_input("size", &input)
+                .on_input(Message::FontSizeInputChanged)
+                .padding(5)
+                .width(60),
+            button(text("+").size(15))
+                .on_press(Message::ZoomIn)
+                .padding([0, 8]),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        if !is_valid {
+            font_size = font_size.push(
+                error_text(format!(
+                    "Must be between {MIN_FONT_SIZE} and {MAX_FONT_SIZE}"
+                ))
+                .size(15),
+            );
+        }
+
+        let appearance = row![
+            font_size,
+            checkbox("Dark theme", matches!(self.theme, Theme::Dark), |is_dark| {
+                Message::ThemeChanged(app_theme(is_dark))
+            }),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let editing = row![
+            checkbox(
+                "Insert spaces for Tab",
+                self.indent.use_spaces,
+                Message::UseSpacesToggled
+            ),
+            text("Tab width").size(15),
+            pick_list(
+                &indent::WIDTHS[..],
+                Some(self.indent.width),
+                Message::TabWidthSelected
+            )
+            .text_size(15),
+            checkbox("Auto-indent", self.auto_indent, Message::AutoIndentToggled),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let autosave = row![
+            checkbox("Autosave", self.autosave, Message::AutosaveToggled),
+            pick_list(
+                &AUTOSAVE_INTERVALS[..],
+                Some(self.autosave_interval),
+                Message::AutosaveIntervalSelected
+            )
+            .text_size(15),
+            text("seconds").size(15),
+            checkbox("Back up on save", self.backup, Message::BackupToggled),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let saving = row![
+            checkbox(
+                "Trim trailing whitespace",
+                self.trim_whitespace,
+                Message::TrimWhitespaceToggled
+            ),
+            checkbox(
+                "Insert final newline",
+                self.final_newline,
+                Message::FinalNewlineToggled
+            ),
+            checkbox(
+                "Reopen files from last time",
+                self.restore_session,
+                Message::RestoreSessionToggled
+            ),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let mut timestamp = row![
+            text("Timestamp format").size(15),
+            text_input(DEFAULT_TIMESTAMP_FORMAT, &self.timestamp_format)
+                .on_input(Message::TimestampFormatChanged)
+                .padding(5)
+                .width(250),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        timestamp = if is_valid_timestamp_format(&self.timestamp_format) {
+            timestamp.push(
+                text(
+                    chrono::Local::now()
+                        .format(&self.timestamp_format)
+                        .to_string(),
+                )
+                .size(15),
+            )
+        } else {
+            timestamp.push(error_text("Not a valid format").size(15))
+        };
+
+        column![appearance, editing, timestamp, autosave, saving]
+            .spacing(10)
+            .into()
+    }
+
+    fn welcome(&self) -> Element<'_, Message> {
+        let recent_files = Column::with_children(
+            self.session
+                .recent_files
+                .iter()
+                .filter(|path| path.exists())
+                .map(|path| {
+                    button(text(path.display()).size(15))
+                        .on_press(Message::OpenRecent(path.clone()))
+                        .style(theme::Button::Text)
+                        .into()
+                })
+                .collect(),
+        )
+        .spacing(5);
+
+        let actions = row![
+            button(text("New File")).on_press(Message::New),
+            button(text("Open File")).on_press(Message::Open),
+        ]
+        .spacing(10);
+
+        let mut content = column![
+            text("Rio Editor").size(40),
+            text("Start typing, or pick one of these to get going.").size(15),
+            actions,
+        ]
+        .spacing(20);
+
+        if !self.session.recent_files.is_empty() {
+            content =
+                content.push(column![text("Recent files").size(20), recent_files].spacing(10));
+        }
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    /// Asks whether to save the changes `pending` would throw away.
+    fn confirm_discard(&self, pending: PendingAction) -> Element<'_, Message> {
+        let name = self
+            .buffers
+            .get(pending.buffer())
+            .map_or("New File", Buffer::name);
+
+        let question = match pending {
+            PendingAction::CloseTab(_) => format!("Save changes to {name} before closing it?"),
+            PendingAction::Quit(_) => format!("Save changes to {name} before quitting?"),
+        };
+
+        let choices = row![
+            button(text("Save")).on_press(Message::ConfirmDiscard(pending, Choice::Save)),
+            button(text("Discard"))
+                .on_press(Message::ConfirmDiscard(pending, Choice::Discard))
+                .style(theme::Button::Destructive),
+            button(text("Cancel"))
+                .on_press(Message::ConfirmDiscard(pending, Choice::Cancel))
+                .style(theme::Button::Secondary),
+        ]
+        .spacing(10);
+
+        let content = column![
+            text("Unsaved changes").size(25),
+            text(question).size(15),
+            choices
+        ]
+        .spacing(20);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    /// Selects the match `direction` picks relative to the cursor.
+    fn find(&mut self, direction: fn(&mut Search, (usize, usize)) -> Option<search::Match>) {
+        let buffer = &mut self.buffers[self.active];
+
+        if let Some(search) = &mut self.search {
+            if let Some(found) = direction(search, buffer.content.cursor_position()) {
+                buffer.select(found.line, found.start, found.end);
+            }
+        }
+    }
+
+    fn refresh_search(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.refresh(&buffer::raw_text(&self.buffers[self.active].content));
+        }
+    }
+
+    /// Starts loading the next dropped file, skipping folders.
+    fn open_dropped(&mut self) -> Command<Message> {
+        while let Some(path) = self.dropped.pop_front() {
+            if path.is_dir() {
+                self.notice = Some(format!("{} is a folder", path.display()));
+                continue;
+            }
+
+            self.is_loading = true;
+
+            return Command::perform(load_file(path), Message::FileOpened);
+        }
+
+        Command::none()
+    }
+
+    /// Reads the file of the active buffer again, as `encoding` if given,
+    /// asking first if that would throw away unsaved changes.
+    ///
+    /// Without an encoding the file is read in the one picked for it last,
+    /// or the one it is in now, rather than guessed again.
+    fn reload(&mut self, encoding: Option<TextEncoding>) -> Command<Message> {
+        let index = self.active;
+        let buffer = self.buffer();
+
+        let Some(path) = buffer.path.clone() else {
+            return Command::none();
+        };
+
+        let encoding = encoding
+            .or_else(|| {
+                self.session
+                    .encodings
+                    .get(&path)
+                    .and_then(|name| TextEncoding::from_name(name))
+            })
+            .unwrap_or(buffer.encoding);
+
+        let is_dirty = buffer.is_dirty;
+        let name = buffer.name().to_owned();
+        self.is_loading = true;
+
+        if is_dirty {
+            return Command::perform(confirm_reload(name), move |confirmed| {
+                Message::ReloadConfirmed(index, encoding, confirmed)
+            });
+        }
+
+        Command::perform(load_file_as(path, encoding), move |result| {
+            Message::FileReloaded(index, result)
+        })
+    }
+
+    fn save(&mut self, index: usize, save_as: bool) -> Command<Message> {
+        let buffer = &self.buffers[index];
+
+        if let (Some(path), false, false) = (&buffer.path, buffer.is_writable, save_as) {
+            let ask = confirm_save_elsewhere(buffer.name().to_owned());
+
+            self.error = Some(Error::ReadOnlyFile(path.clone()));
+            self.is_loading = true;
+
+            return Command::perform(ask, move |confirmed| {
+                Message::SaveElsewhereConfirmed(index, confirmed)
+            });
+        }
+
+        if buffer.mixed_line_endings {
+            let ask = confirm_line_endings(buffer.name().to_owned(), buffer.line_ending);
+            self.is_loading = true;
+
+            return Command::perform(ask, move |confirmed| {
+                Message::LineEndingsConfirmed(index, save_as, confirmed)
+            });
+        }
+
+        if self.trim_whitespace {
+            self.buffers[index].trim_trailing_whitespace();
+        }
+
+        if self.final_newline {
+            self.buffers[index].insert_final_newline();
+        }
+
+        self.refresh_search();
+
+        let buffer = &self.buffers[index];
+
+        if buffer.changed_on_disk && !save_as {
+            let name = buffer.name().to_owned();
+            self.is_loading = true;
+
+            return Command::perform(confirm_overwrite(name), move |confirmed| {
+                Message::OverwriteConfirmed(index, confirmed)
+            });
+        }
+
+        self.write(index, save_as)
+    }
+
+    fn write(&mut self, index: usize, save_as: bool) -> Command<Message> {
+        let buffer = &self.buffers[index];
+        let mut text = buffer::raw_text(&buffer.content);
+        let fingerprint = buffer.fingerprint(&text);
+
+        // Autosaves write the buffer as is, so the line break is added here
+        // too rather than only in `save`.
+        if self.final_newline && !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+
+        let text = buffer.line_ending.apply(&text);
+        let path = buffer.path.clone();
+
+        let bytes = match encoding::encode(&text, buffer.encoding) {
+            Ok(bytes) => bytes,
+            Err(character) => {
+                let encoding = buffer.encoding.encoding.name();
+                self.pending = None;
+                self.error = Some(Error::UnencodableCharacter(character, encoding));
+                return Command::none();
+            }
+        };
+
+        self.is_loading = true;
+
+        let on_saved = move |result| Message::FileSaved(index, result);
+
+        if save_as {
+            Command::perform(
+                save_file_as(path, bytes, fingerprint, self.backup),
+                on_saved,
+            )
+        } else {
+            Command::perform(save_file(path, bytes, fingerprint, self.backup), on_saved)
+        }
+    }
+
+    fn perform(&mut self, pending: PendingAction) -> Command<Message> {
+        match pending {
+            PendingAction::CloseTab(index) => {
+                if index >= self.buffers.len() {
+                    return Command::none();
+                }
+
+                self.buffers.remove(index);
+                self.error = None;
+
+                if self.buffers.is_empty() {
+                    self.buffers.push(Buffer::new());
+                    self.show_welcome = true;
+                }
+
+                if index < self.active {
+                    self.active -= 1;
+                }
+
+                self.active = self.active.min(self.buffers.len() - 1);
+                self.refresh_search();
+                self.save_session()
+            }
+            PendingAction::Quit(index) => {
+                self.discarded.push(index);
+                self.close_window()
+            }
+        }
+    }
+
+    /// Closes the window, unless a buffer still has unsaved changes the user
+    /// hasn't been asked about yet.
+    fn close_window(&mut self) -> Command<Message> {
+        let held_up = self.buffers.iter().enumerate().position(|(index, buffer)| {
+            buffer.is_dirty && !buffer.is_pristine() && !self.discarded.contains(&index)
+        });
+
+        match held_up {
+            Some(index) => {
+                self.active = index;
+                self.confirming = Some(PendingAction::Quit(index));
+                self.is_loading = true;
+                Command::none()
+            }
+            // Closing right away could end the editor before the session
+            // is written.
+            None => {
+                self.is_loading = true;
+                self.is_closing = true;
+                self.save_session()
+            }
+        }
+    }
+
+    /// Closes the window if it is closing and nothing is being written
+    /// anymore.
+    fn close_if_done(&self) -> Command<Message> {
+        if self.is_closing && self.session_writes.is_idle() && self.config_writes.is_idle() {
+            window::close()
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Sends typing to the buffer again after a prompt took the keyboard.
+    ///
+    /// The text editor of this iced has no id to focus it with, so rather
+    /// than a focus command, the keys nothing else takes are forwarded
+    /// until the editor is clicked.
+    fn return_keyboard(&mut self) -> Command<Message> {
+        self.keys_to_buffer = true;
+        Command::none()
+    }
+
+    fn zoom(&mut self, font_size: f32) -> Command<Message> {
+        self.font_size = font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        self.font_size_input = None;
+        self.config.font_size = Some(self.font_size);
+
+        self.flash(format!("Font size: {}", self.font_size));
+        self.save_config()
+    }
+
+    /// Shows `message` in the status bar for a couple of seconds.
+    fn flash(&mut self, message: impl Into<String>) {
+        self.status_message = Some((message.into(), Instant::now()));
+    }
+
+    /// Writes the settings to disk.
+    ///
+    /// Zooming or typing a font size changes them many times in a row, so
+    /// only one write runs at a time and the last change is written after
+    /// it.
+    fn save_config(&mut self) -> Command<Message> {
+        if self.config_writes.start() {
+            Command::perform(self.config.clone().save(), Message::ConfigSaved)
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Writes the session to disk, remembering the open files and which one
+    /// is active so they are reopened on the next launch, and where the
+    /// cursor was in every open file.
+    ///
+    /// Only one write runs at a time, so they can't land out of order.
+    fn save_session(&mut self) -> Command<Message> {
+        self.update_session();
+
+        if self.session_writes.start() {
+            Command::perform(self.session.clone().save(), Message::SessionSaved)
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Brings the session up to date with the open buffers.
+    fn update_session(&mut self) {
+        // An untitled buffer isn't reopened, so no file was active.
+        self.session.last_file = self.buffer().path.clone();
+
+        self.session.open_files = self
+            .buffers
+            .iter()
+            .filter_map(|buffer| buffer.path.clone())
+            .collect();
+
+        for buffer in &self.buffers {
+            if let Some(path) = &buffer.path {
+                self.session
+                    .cursors
+                    .insert(path.clone(), buffer.content.cursor_position());
+            }
+        }
+
+        let buffers = &self.buffers;
+        let recent_files = &self.session.recent_files;
+
+        let is_known = |path: &PathBuf| {
+            recent_files.contains(path)
+                || buffers
+                    .iter()
+                    .any(|buffer| buffer.path.as_ref() == Some(path))
+        };
+
+        self.session.cursors.retain(|path, _| is_known(path));
+        self.session.encodings.retain(|path, _| is_known(path));
+    }
+}
+
+/// The window settings that bring back the size and position the window had
+/// when the editor was last closed.
+///
+/// A position too far out to be on any monitor is dropped, leaving the
+/// placement to the platform.
+fn window_settings(session: &Session) -> window::Settings {
+    let defaults = window::Settings::default();
+    let size = session
+        .window_size
+        .map_or(defaults.size, |(width, height)| {
+            (width.max(MIN_WINDOW_SIZE.0), height.max(MIN_WINDOW_SIZE.1))
+        });
+    let position = session
+        .window_position
+        .filter(|(x, y)| {
+            let width = size.0 as i32;
+
+            (-width / 2..MAX_WINDOW_OFFSET).contains(x) && (0..MAX_WINDOW_OFFSET).contains(y)
+        })
+        .map_or(defaults.position, |(x, y)| window::Position::Specific(x, y));
+
+    window::Settings {
+        size,
+        position,
+        ..defaults
+    }
+}
+
+fn app_theme(is_dark: bool) -> Theme {
+    if is_dark {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
+}
+
+fn highlighter_theme(is_dark: bool) -> highlighter::Theme {
+    if is_dark {
+        highlighter::Theme::SolarizedDark
+    } else {
+        highlighter::Theme::InspiredGitHub
+    }
+}
+
+fn action<'a>(
+    content: Element<'a, Message>,
+    label: &str,
+    on_press: Option<Message>,
+) -> Element<'a, Message> {
+    let is_disabled = on_press.is_none();
+
+    tooltip(
+        button(container(content).width(30).center_x())
+            .on_press_maybe(on_press)
+            .padding([5, 10])
+            .style(if is_disabled {
+                theme::Button::Secondary
+            } else {
+                theme::Button::Primary
+            }),
+        label,
+        tooltip::Position::FollowCursor,
+    )
+    .style(theme::Container::Box)
+    .into()
+}
+
+fn shortcut(key_code: keyboard::KeyCode, modifiers: keyboard::Modifiers) -> Option<Message> {
+    match key_code {
+        keyboard::KeyCode::N if modifiers.command() => Some(Message::New),
+        keyboard::KeyCode::W if modifiers.command() => Some(Message::Close),
+        keyboard::KeyCode::O if modifiers.command() => Some(Message::Open),
+        keyboard::KeyCode::S if modifiers.command() && modifiers.shift() => Some(Message::SaveAs),
+        keyboard::KeyCode::S if modifiers.command() => Some(Message::Save),
+        keyboard::KeyCode::C if modifiers.command() && modifiers.shift() => Some(Message::CopyPath),
+        keyboard::KeyCode::P if modifiers.command() && modifiers.shift() => {
+            Some(Message::TogglePalette)
+        }
+        keyboard::KeyCode::F if modifiers.command() => Some(Message::ToggleFind),
+        keyboard::KeyCode::G if modifiers.command() => Some(Message::OpenGoToLine),
+        keyboard::KeyCode::I if modifiers.alt() && modifiers.shift() => {
+            Some(Message::OpenLinePrefix)
+        }
+        keyboard::KeyCode::Plus | keyboard::KeyCode::Equals | keyboard::KeyCode::NumpadAdd
+            if modifiers.command() =>
+        {
+            Some(Message::ZoomIn)
+        }
+        keyboard::KeyCode::Minus | keyboard::KeyCode::NumpadSubtract if modifiers.command() => {
+            Some(Message::ZoomOut)
+        }
+        keyboard::KeyCode::Key0 | keyboard::KeyCode::Numpad0 if modifiers.command() => {
+            Some(Message::ZoomReset)
+        }
+        keyboard::KeyCode::R if modifiers.command() => Some(Message::Revert),
+        keyboard::KeyCode::F5 => Some(Message::Reload),
+        keyboard::KeyCode::Escape => Some(Message::Escape),
+        // Only move through the command palette, and are ignored otherwise.
+        keyboard::KeyCode::Up if modifiers.is_empty() => Some(Message::PaletteMoved(false)),
+        keyboard::KeyCode::Down if modifiers.is_empty() => Some(Message::PaletteMoved(true)),
+        _ => None,
+    }
+}
+
+/// What the text editor does with `key_code` when it has the keyboard, for
+/// the keys that aren't typed characters.
+fn editor_key(
+    key_code: keyboard::KeyCode,
+    modifiers: keyboard::Modifiers,
+) -> Option<text_editor::Action> {
+    let motion = match key_code {
+        keyboard::KeyCode::Enter | keyboard::KeyCode::NumpadEnter => {
+            return Some(text_editor::Action::Edit(text_editor::Edit::Enter));
+        }
+        keyboard::KeyCode::Backspace => {
+            return Some(text_editor::Action::Edit(text_editor::Edit::Backspace));
+        }
+        keyboard::KeyCode::Delete => {
+            return Some(text_editor::Action::Edit(text_editor::Edit::Delete));
+        }
+        keyboard::KeyCode::Left if modifiers.command() => text_editor::Motion::WordLeft,
+        keyboard::KeyCode::Right if modifiers.command() => text_editor::Motion::WordRight,
+        keyboard::KeyCode::Home if modifiers.command() => text_editor::Motion::DocumentStart,
+        keyboard::KeyCode::End if modifiers.command() => text_editor::Motion::DocumentEnd,
+        keyboard::KeyCode::Left => text_editor::Motion::Left,
+        keyboard::KeyCode::Right => text_editor::Motion::Right,
+        keyboard::KeyCode::Up => text_editor::Motion::Up,
+        keyboard::KeyCode::Down => text_editor::Motion::Down,
+        keyboard::KeyCode::Home => text_editor::Motion::Home,
+        keyboard::KeyCode::End => text_editor::Motion::End,
+        keyboard::KeyCode::PageUp => text_editor::Motion::PageUp,
+        keyboard::KeyCode::PageDown => text_editor::Motion::PageDown,
+        _ => return None,
+    };
+
+    Some(if modifiers.shift() {
+        text_editor::Action::Select(motion)
+    } else {
+        text_editor::Action::Move(motion)
+    })
+}
+
+/// The shortcuts that change the text or the selection of the buffer, which
+/// only apply while no text input has the keyboard.
+fn editing_shortcut(
+    key_code: keyboard::KeyCode,
+    modifiers: keyboard::Modifiers,
+) -> Option<Message> {
+    match key_code {
+        keyboard::KeyCode::Z if modifiers.command() && modifiers.shift() => Some(Message::Redo),
+        keyboard::KeyCode::Z if modifiers.command() => Some(Message::Undo),
+        keyboard::KeyCode::Y if modifiers.command() => Some(Message::Redo),
+        keyboard::KeyCode::A if modifiers.command() => Some(Message::SelectAll),
+        keyboard::KeyCode::K if modifiers.command() && modifiers.shift() => {
+            Some(Message::DeleteLines)
+        }
+        keyboard::KeyCode::J if modifiers.command() => Some(Message::JoinLines),
+        keyboard::KeyCode::U if modifiers.command() && modifiers.shift() => {
+            Some(Message::ChangeCase(Case::Title))
+        }
+        keyboard::KeyCode::U if modifiers.command() => Some(Message::ChangeCase(Case::Upper)),
+        keyboard::KeyCode::L if modifiers.command() => Some(Message::ChangeCase(Case::Lower)),
+        keyboard::KeyCode::D if modifiers.command() => Some(Message::Duplicate),
+        keyboard::KeyCode::Slash if modifiers.command() => Some(Message::ToggleComment),
+        keyboard::KeyCode::Semicolon if modifiers.command() => Some(Message::InsertTimestamp),
+        // Shift reverses the order and Alt ignores case.
+        keyboard::KeyCode::F9 => Some(Message::SortLines(Sort {
+            reverse: modifiers.shift(),
+            ignore_case: modifiers.alt(),
+        })),
+        _ => None,
+    }
+}
+
+fn command_palette(palette: &Palette) -> Element<'_, Message> {
+    let entries = Column::with_children(
+        palette
+            .matches()
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let label = row![
+                    text(entry.label).size(15),
+                    horizontal_space(Length::Fill),
+                    text(entry.shortcut.unwrap_or_default()).size(13),
+                ]
+                .spacing(10);
+
+                button(label)
+                    .on_press(Message::RunCommand(Box::new(entry.message)))
+                    .width(Length::Fill)
+                    .style(if index == palette.selected {
+                        theme::Button::Primary
+                    } else {
+                        theme::Button::Text
+                    })
+                    .into()
+            })
+            .collect(),
+    );
+
+    container(
+        column![
+            text_input("Type a command", &palette.query)
+                .id(palette_input())
+                .on_input(Message::PaletteQueryChanged)
+                .on_submit(Message::PaletteSubmitted)
+                .padding(5),
+            scrollable(entries).height(250),
+        ]
+        .spacing(10),
+    )
+    .padding(10)
+    .style(theme::Container::Box)
+    .into()
+}
+
+fn find_bar(search: &Search) -> Element<'_, Message> {
+    let find = row![
+        text_input("Find", &search.query)
+            .id(find_input())
+            .on_input(Message::FindQueryChanged)
+            .on_submit(Message::FindNext)
+            .padding(5)
+            .width(300),
+        button(text("Previous")).on_press(Message::FindPrevious),
+        button(text("Next")).on_press(Message::FindNext),
+        checkbox("Regex", search.is_regex, Message::RegexToggled),
+        match &search.error {
+            Some(error) => error_text(error).size(15),
+            None => text(search.summary()).size(15),
+        },
+        horizontal_space(Length::Fill),
+        button(text("\u{00D7}"))
+            .on_press(Message::CloseFind)
+            .style(theme::Button::Text),
+    ]
+    .spacing(10)
+    .align_items(Alignment::Center);
+
+    let has_matches = !search.matches.is_empty();
+
+    let replace = row![
+        text_input("Replace", &search.replacement)
+            .on_input(Message::ReplacementChanged)
+            .on_submit(Message::Replace)
+            .padding(5)
+            .width(300),
+        button(text("Replace")).on_press_maybe(has_matches.then_some(Message::Replace)),
+        button(text("Replace All")).on_press_maybe(has_matches.then_some(Message::ReplaceAll)),
+    ]
+    .spacing(10)
+    .align_items(Alignment::Center);
+
+    column![find, replace].spacing(10).into()
+}
+
+/// The size of a file and when it was last modified, in local time.
+fn file_info(stamp: FileStamp) -> String {
+    let size = file_size(stamp.len);
+
+    match stamp.modified {
+        Some(modified) => format!("{size} \u{b7} modified {}", time_ago(modified)),
+        None => size,
+    }
+}
+
+/// How long ago `time` was, roughly, falling back to the date for anything
+/// older than a week.
+fn time_ago(time: SystemTime) -> String {
+    let Ok(elapsed) = SystemTime::now().duration_since(time) else {
+        return String::from("just now");
+    };
+
+    match elapsed.as_secs() {
+        0..=59 => String::from("just now"),
+        seconds @ 60..=3599 => format!("{} min ago", seconds / 60),
+        seconds @ 3600..=86_399 => format!("{} h ago", seconds / 3600),
+        86_400..=172_799 => String::from("yesterday"),
+        seconds @ 172_800..=604_799 => format!("{} days ago", seconds / 86_400),
+        _ => chrono::DateTime::<chrono::Local>::from(time)
+            .format("on %Y-%m-%d")
+            .to_string(),
+    }
+}
+
+fn file_size(len: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+
+    if len < 1024 {
+        return format!("{len} bytes");
+    }
+
+    let mut size = len as f64 / 1024.0;
+    let mut unit = UNITS[0];
+
+    for next in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+
+        size /= 1024.0;
+        unit = next;
+    }
+
+    format!("{size:.1} {unit}")
+}
+
+/// Explains a failed read or write in words, since the bare error kinds are
+/// rather cryptic.
+fn io_error_message(kind: io::ErrorKind) -> String {
+    match kind {
+        io::ErrorKind::PermissionDenied => {
+            String::from("Permission denied: you don't have access to this file or folder")
+        }
+        io::ErrorKind::NotFound => String::from("The file or folder could not be found"),
+        io::ErrorKind::AlreadyExists => String::from("A file with that name already exists"),
+        kind => format!("Something went wrong: {kind}"),
+    }
+}
+
+/// Whether chrono can format a time with `format`, which it panics on
+/// otherwise.
+fn is_valid_timestamp_format(format: &str) -> bool {
+    !format.is_empty()
+        && chrono::format::StrftimeItems::new(format)
+            .all(|item| !matches!(item, chrono::format::Item::Error))
+}
+
+fn error_text<'a>(message: impl ToString) -> Text<'a> {
+    text(message).style(Color::from_rgb(0.9, 0.3, 0.3))
+}
+
+fn find_input() -> text_input::Id {
+    text_input::Id::new("find")
+}
+
+fn palette_input() -> text_input::Id {
+    text_input::Id::new("palette")
+}
+
+fn go_to_line_input() -> text_input::Id {
+    text_input::Id::new("go-to-line")
+}
+
+fn line_prefix_input() -> text_input::Id {
+    text_input::Id::new("line-prefix")
+}
+
+fn tab_button<'a>(index: usize, buffer: &Buffer, is_active: bool) -> Element<'a, Message> {
+    let label = if buffer.is_dirty {
+        format!("{}*", buffer.name())
+    } else {
+        buffer.name().to_owned()
+    };
+
+    let select = button(text(label).size(14))
+        .on_press(Message::TabSelected(index))
+        .padding([2, 10])
+        .style(if is_active {
+            theme::Button::Primary
+        } else {
+            theme::Button::Secondary
+        });
+
+    let close = button(text("\u{00D7}").size(14))
+        .on_press(Message::TabClosed(index))
+        .padding([2, 5])
+        .style(theme::Button::Text);
+
+    row![select, close].into()
+}
+
+fn new_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E800}')
+}
+
+fn save_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E801}')
+}
+
+fn save_as_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E80B}')
+}
+
+fn load_icon<'a>() -> Element<'a, Message> {
+    icon('\u{F115}')
+}
+
+fn preferences_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E802}')
+}
+
+fn undo_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E803}')
+}
+
+fn redo_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E804}')
+}
+
+fn copy_path_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E805}')
+}
+
+fn reveal_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E80C}')
+}
+
+fn reload_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E806}')
+}
+
+fn revert_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E807}')
+}
+
+fn lock_icon<'a>(is_locked: bool) -> Element<'a, Message> {
+    icon(if is_locked { '\u{E808}' } else { '\u{E809}' })
+}
+
+fn theme_icon<'a>() -> Element<'a, Message> {
+    icon('\u{E80A}')
+}
+
+fn icon<'a>(codepoint: char) -> Element<'a, Message> {
+    const ICON_FONT: Font = Font::with_name("rio-icons");
+
+    text(codepoint).font(ICON_FONT).into()
+}
+
+async fn confirm_overwrite(name: String) -> bool {
+    let result = rfd::AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("File changed on disk")
+        .set_description(format!(
+            "{name} was changed by another program. Overwrite those changes?"
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .await;
+
+    matches!(result, rfd::MessageDialogResult::Yes)
+}
+
+async fn confirm_line_endings(name: String, line_ending: LineEnding) -> bool {
+    let result = rfd::AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Mixed line endings")
+        .set_description(format!(
+            "{name} uses both LF and CRLF line endings. Save it with {line_ending} everywhere?"
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .await;
+
+    matches!(result, rfd::MessageDialogResult::Yes)
+}
+
+async fn confirm_reload(name: String) -> bool {
+    let result = rfd::AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Unsaved changes")
+        .set_description(format!(
+            "Reloading {name} from disk will discard your unsaved changes. Reload anyway?"
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .await;
+
+    matches!(result, rfd::MessageDialogResult::Yes)
+}
+
+async fn confirm_lossy_open(name: String, encoding: &'static str) -> bool {
+    let result = rfd::AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title(format!("File is not valid {encoding}"))
+        .set_description(format!(
+            "{name} is not valid {encoding}. Open it anyway, replacing the invalid bytes?"
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .await;
+
+    matches!(result, rfd::MessageDialogResult::Yes)
+}
+
+async fn confirm_save_elsewhere(name: String) -> bool {
+    let result = rfd::AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Permission denied")
+        .set_description(format!(
+            "You don't have permission to save {name} here. Save it somewhere else?"
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .await;
+
+    matches!(result, rfd::MessageDialogResult::Yes)
+}
+
+async fn pick_file() -> Result<OpenedFile, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Choose a text file")
+        .pick_file()
+        .await
+        .ok_or(Error::DialogError)?;
+
+    load_file(handle.path().to_owned()).await
+}
+
+async fn load_file(path: PathBuf) -> Result<OpenedFile, Error> {
+    read_file(path, false).await
+}
+
+/// Loads the file at `path`, replacing any bytes that aren't valid in its
+/// encoding.
+async fn load_file_lossy(path: PathBuf) -> Result<OpenedFile, Error> {
+    read_file(path, true).await
+}
+
+/// Loads the file at `path` as `encoding`, whatever it looks like.
+async fn load_file_as(path: PathBuf, encoding: TextEncoding) -> Result<OpenedFile, Error> {
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|error| Error::file(FileOperation::Open, &path, error))?;
+
+    let stamp = file_stamp(&path).await;
+    let is_writable = is_writable(&path).await;
+
+    Ok(OpenedFile {
+        text: Arc::new(encoding::decode_as(&bytes, encoding)),
+        path,
+        encoding,
+        stamp,
+        is_writable,
+    })
+}
+
+async fn read_file(path: PathBuf, lossy: bool) -> Result<OpenedFile, Error> {
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|error| Error::file(FileOperation::Open, &path, error))?;
+
+    let stamp = file_stamp(&path).await;
+    let is_writable = is_writable(&path).await;
+
+    match encoding::decode(&bytes, lossy) {
+        Ok((text, encoding)) => Ok(OpenedFile {
+            path,
+            text: Arc::new(text),
+            encoding,
+            stamp,
+            is_writable,
+        }),
+        Err(encoding::DecodeError::Binary) => Err(Error::BinaryFile(path)),
+        Err(encoding::DecodeError::Malformed(encoding)) => {
+            Err(Error::EncodingError(path, encoding.name()))
+        }
+    }
+}
+
+async fn save_file(
+    path: Option<PathBuf>,
+    bytes: Vec<u8>,
+    fingerprint: Fingerprint,
+    backup: bool,
+) -> Result<SavedFile, Error> {
+    let path = if let Some(path) = path {
+        path
+    } else {
+        pick_save_path(None).await?
+    };
+
+    let backup_error = if backup {
+        back_up(&path).await.err().map(|error| error.kind())
+    } else {
+        None
+    };
+
+    atomic::write(&path, &bytes)
+        .await
+        .map_err(|error| Error::file(FileOperation::Save, &path, error))?;
+
+    let stamp = file_stamp(&path).await;
+
+    Ok(SavedFile {
+        path,
+        stamp,
+        fingerprint,
+        backup_error,
+    })
+}
+
+/// Copies the file at `path` to `<name>.bak`, replacing the previous backup.
+///
+/// Files that don't exist yet have nothing to back up, and backups aren't
+/// backed up themselves.
+async fn back_up(path: &Path) -> io::Result<()> {
+    let is_backup = path.extension().is_some_and(|extension| extension == "bak");
+
+    if is_backup || !tokio::fs::try_exists(path).await? {
+        return Ok(());
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+
+    tokio::fs::copy(path, backup).await.map(|_| ())
+}
+
+async fn save_file_as(
+    current: Option<PathBuf>,
+    bytes: Vec<u8>,
+    fingerprint: Fingerprint,
+    backup: bool,
+) -> Result<SavedFile, Error> {
+    let path = pick_save_path(current.as_deref()).await?;
+
+    save_file(Some(path), bytes, fingerprint, backup).await
+}
+
+/// Whether the file at `path` can be written to, which its permissions alone
+/// don't tell for read-only mounts or files owned by someone else.
+///
+/// Opening for writing doesn't truncate, so the file is left as it was.
+async fn is_writable(path: &Path) -> bool {
+    tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await
+        .is_ok()
+}
+
+async fn file_stamp(path: &Path) -> Option<FileStamp> {
+    tokio::fs::metadata(path)
+        .await
+        .ok()
+        .map(|metadata| FileStamp::new(&metadata))
+}
+
+/// The current stamps of `paths`, or `None` for files that can't be found.
+async fn stamp_files(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<FileStamp>)> {
+    let mut stamps = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let stamp = file_stamp(&path).await;
+        stamps.push((path, stamp));
+    }
+
+    stamps
+}
+
+/// Watches the directories of `paths` and reports which of the files in
+/// them changed, once the events have settled down.
+async fn watch_files(paths: Vec<PathBuf>, mut output: mpsc::Sender<Message>) -> Never {
+    let (sender, mut events) = mpsc::unbounded();
+
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = sender.unbounded_send(event);
+        }
+    });
+
+    // Without a watcher nothing will ever be reported, which is no worse
+    // than before watching was supported.
+    let Ok(mut watcher) = watcher else {
+        return future::pending().await;
+    };
+
+    let mut directories: Vec<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+    directories.sort();
+    directories.dedup();
+
+    for directory in directories {
+        let _ = watcher.watch(directory, notify::RecursiveMode::NonRecursive);
+    }
+
+    loop {
+        let Some(event) = events.next().await else {
+            return future::pending().await;
+        };
+
+        let mut changed = event.paths;
+
+        tokio::time::sleep(WATCH_DEBOUNCE).await;
+
+        while let Ok(Some(event)) = events.try_next() {
+            changed.extend(event.paths);
+        }
+
+        changed.retain(|path| paths.contains(path));
+        changed.sort();
+        changed.dedup();
+
+        if !changed.is_empty() {
+            let _ = output.send(Message::FileChangedExternally(changed)).await;
+        }
+    }
+}
+
+/// Opens the folder holding `path` in the file manager.
+async fn reveal_in_folder(path: PathBuf) -> Result<(), Error> {
+    let directory = path
+        .parent()
+        .ok_or(Error::IoError(io::ErrorKind::NotFound))?;
+
+    open::that_detached(directory)
+        .map_err(|error| Error::file(FileOperation::Open, directory, error))
+}
+
+async fn pick_save_path(current: Option<&Path>) -> Result<PathBuf, Error> {
+    let mut dialog = rfd::AsyncFileDialog::new().set_title("Choose a file name");
+
+    if let Some(current) = current {
+        if let Some(name) = current.file_name().and_then(|name| name.to_str()) {
+            dialog = dialog.set_file_name(name);
+        }
+
+        if let Some(directory) = current.parent() {
+            dialog = dialog.set_directory(directory);
+        }
+    }
+
+    dialog
+        .save_file()
+        .await
+        .ok_or(Error::DialogError)
+        .map(|handle| handle.path().to_owned())
+}
+
+/// The border drawn around the window while files are dragged over it.
+struct DropTarget;
+
+impl container::StyleSheet for DropTarget {
+    type Style = Theme;
+
+    fn appearance(&self, theme: &Theme) -> container::Appearance {
+        container::Appearance {
+            border_width: 2.0,
+            border_color: theme.palette().primary,
+            ..container::Appearance::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecentFile(PathBuf);
+
+impl fmt::Display for RecentFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.display())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Error {
+    DialogError,
+    IoError(io::ErrorKind),
+    /// Doing something with the file at this path failed, with the system's
+    /// description of why, since `io::Error` itself can't be cloned.
+    FileError(FileOperation, PathBuf, io::ErrorKind, String),
+    /// The file at this path is not valid in the named encoding.
+    EncodingError(PathBuf, &'static str),
+    /// The file at this path can't be written to.
+    ReadOnlyFile(PathBuf),
+    /// The file at this path doesn't look like text.
+    BinaryFile(PathBuf),
+    /// The character can't be saved in the named encoding.
+    UnencodableCharacter(char, &'static str),
+}
+
+impl Error {
+    fn file(operation: FileOperation, path: &Path, error: io::Error) -> Self {
+        Error::FileError(operation, path.to_owned(), error.kind(), error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FileOperation {
+    Open,
+    Save,
+}
+
+impl fmt::Display for FileOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileOperation::Open => write!(f, "open"),
+            FileOperation::Save => write!(f, "save"),
+        }
+    }
 }
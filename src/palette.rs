@@ -0,0 +1,221 @@
+use crate::buffer::{Case, Sort};
+use crate::Message;
+
+/// A command that can be run from the palette.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub label: &'static str,
+    pub shortcut: Option<&'static str>,
+    pub message: Message,
+}
+
+/// Every command the palette offers, in the order they are listed.
+///
+/// New commands only need a line here to show up.
+pub fn entries() -> Vec<Entry> {
+    let entries = [
+        ("New File", Some("Ctrl+N"), Message::New),
+        ("Open File", Some("Ctrl+O"), Message::Open),
+        ("Save", Some("Ctrl+S"), Message::Save),
+        ("Save As", Some("Ctrl+Shift+S"), Message::SaveAs),
+        ("Close Tab", Some("Ctrl+W"), Message::Close),
+        ("Reload From Disk", Some("F5"), Message::Reload),
+        ("Revert to Saved", Some("Ctrl+R"), Message::Revert),
+        ("Copy File Path", Some("Ctrl+Shift+C"), Message::CopyPath),
+        ("Open Containing Folder", None, Message::RevealInFolder),
+        ("Toggle Read-Only", None, Message::ToggleReadOnly),
+        ("Undo", Some("Ctrl+Z"), Message::Undo),
+        ("Redo", Some("Ctrl+Y"), Message::Redo),
+        ("Select All", Some("Ctrl+A"), Message::SelectAll),
+        ("Find and Replace", Some("Ctrl+F"), Message::ToggleFind),
+        ("Go to Line", Some("Ctrl+G"), Message::OpenGoToLine),
+        ("Toggle Comment", Some("Ctrl+/"), Message::ToggleComment),
+        ("Insert Timestamp", Some("Ctrl+;"), Message::InsertTimestamp),
+        ("Duplicate", Some("Ctrl+D"), Message::Duplicate),
+        ("Delete Lines", Some("Ctrl+Shift+K"), Message::DeleteLines),
+        ("Join Lines", Some("Ctrl+J"), Message::JoinLines),
+        (
+            "Insert at Start of Lines",
+            Some("Alt+Shift+I"),
+            Message::OpenLinePrefix,
+        ),
+        ("Move Lines Up", Some("Alt+Up"), Message::MoveLinesUp),
+        ("Move Lines Down", Some("Alt+Down"), Message::MoveLinesDown),
+        (
+            "Sort Lines",
+            Some("F9"),
+            Message::SortLines(Sort {
+                reverse: false,
+                ignore_case: false,
+            }),
+        ),
+        (
+            "Sort Lines in Reverse",
+            Some("Shift+F9"),
+            Message::SortLines(Sort {
+                reverse: true,
+                ignore_case: false,
+            }),
+        ),
+        (
+            "Sort Lines Ignoring Case",
+            Some("Alt+F9"),
+            Message::SortLines(Sort {
+                reverse: false,
+                ignore_case: true,
+            }),
+        ),
+        (
+            "Uppercase",
+            Some("Ctrl+U"),
+            Message::ChangeCase(Case::Upper),
+        ),
+        (
+            "Lowercase",
+            Some("Ctrl+L"),
+            Message::ChangeCase(Case::Lower),
+        ),
+        (
+            "Title Case",
+            Some("Ctrl+Shift+U"),
+            Message::ChangeCase(Case::Title),
+        ),
+        ("Zoom In", Some("Ctrl++"), Message::ZoomIn),
+        ("Zoom Out", Some("Ctrl+-"), Message::ZoomOut),
+        ("Reset Zoom", Some("Ctrl+0"), Message::ZoomReset),
+        ("Toggle Light/Dark Theme", None, Message::ToggleTheme),
+        ("Preferences", None, Message::TogglePreferences),
+    ];
+
+    entries
+        .into_iter()
+        .map(|(label, shortcut, message)| Entry {
+            label,
+            shortcut,
+            message,
+        })
+        .collect()
+}
+
+/// What is typed in the palette and which of the matching entries is
+/// highlighted.
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl Palette {
+    /// The entries matching the query, best first.
+    pub fn matches(&self) -> Vec<Entry> {
+        filter(&self.query)
+    }
+
+    /// Replaces the query, highlighting the best match again.
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+    }
+
+    /// Highlights the next or previous match, wrapping around at either end.
+    pub fn move_selection(&mut self, down: bool) {
+        let count = self.matches().len();
+
+        if count == 0 {
+            return;
+        }
+
+        self.selected = if down {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+
+    /// The highlighted entry, if anything matches.
+    pub fn selection(&self) -> Option<Entry> {
+        self.matches().into_iter().nth(self.selected)
+    }
+}
+
+/// The entries whose label contains the letters of `query` in order,
+/// ignoring case, best match first.
+pub fn filter(query: &str) -> Vec<Entry> {
+    let mut matches: Vec<(u32, Entry)> = entries()
+        .into_iter()
+        .filter_map(|entry| Some((score(entry.label, query)?, entry)))
+        .collect();
+
+    // The sort is stable, so equally good matches keep the table's order.
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// How well `label` matches `query`, or `None` if it does not.
+///
+/// Each letter of the query is matched to the earliest one left in the
+/// label. Letters that start a word or directly follow the previous match
+/// count for more, so "sa" ranks "Save" above "Select All".
+fn score(label: &str, query: &str) -> Option<u32> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = next + label[next..].iter().position(|&c| c == wanted)?;
+
+        score += 1;
+
+        if found == 0 || !label[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+
+        previous = Some(found);
+        next = found + 1;
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, filter, score};
+
+    #[test]
+    fn ranks_a_prefix_above_a_contiguous_match_above_scattered_letters() {
+        let prefix = score("save", "sav").unwrap();
+        let contiguous = score("xsavex", "sav").unwrap();
+        let scattered = score("xsxaxv", "sav").unwrap();
+
+        assert!(prefix > contiguous);
+        assert!(contiguous > scattered);
+    }
+
+    #[test]
+    fn ranks_save_above_select_all() {
+        assert!(score("Save", "sa") > score("Select All", "sa"));
+        assert_eq!(filter("sa")[0].label, "Save");
+    }
+
+    #[test]
+    fn matches_ignoring_case_and_spaces() {
+        assert!(score("Select All", "SEL all").is_some());
+    }
+
+    #[test]
+    fn does_not_match_letters_out_of_order_or_missing() {
+        assert_eq!(score("Save", "as"), None);
+        assert_eq!(score("Save", "sx"), None);
+        assert_eq!(score("Save", "saves"), None);
+    }
+
+    #[test]
+    fn lists_every_entry_for_an_empty_query() {
+        assert_eq!(filter("").len(), entries().len());
+    }
+}
@@ -0,0 +1,385 @@
+use regex::Regex;
+
+/// An occurrence of the query, as a byte range within a single line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    pub query: String,
+    pub replacement: String,
+    /// Whether the query is a regular expression rather than plain text.
+    pub is_regex: bool,
+    /// Why the query failed to compile, in regex mode.
+    pub error: Option<String>,
+    pub matches: Vec<Match>,
+    pub current: Option<usize>,
+    regex: Option<Regex>,
+}
+
+impl Search {
+    /// Starts a search for `query`, treating the match touching the cursor
+    /// (typically the selection the query came from) as the current one.
+    pub fn with_query(query: String, text: &str, cursor: (usize, usize)) -> Self {
+        let mut search = Search {
+            query,
+            ..Search::default()
+        };
+
+        search.refresh(text);
+        search.current = search.matches.iter().position(|found| {
+            (found.line, found.start) == cursor || (found.line, found.end) == cursor
+        });
+
+        search
+    }
+
+    /// Replaces the query and finds its first occurrence at or after the
+    /// current match, so the selection grows in place while typing.
+    pub fn set_query(
+        &mut self,
+        query: String,
+        text: &str,
+        cursor: (usize, usize),
+    ) -> Option<Match> {
+        let from = self
+            .current
+            .and_then(|index| self.matches.get(index))
+            .map_or(cursor, |found| (found.line, found.start));
+
+        self.query = query;
+        self.refresh(text);
+        self.select(
+            self.matches
+                .iter()
+                .position(|found| (found.line, found.start) >= from),
+        )
+    }
+
+    /// Finds the matches again after the text or the query has changed,
+    /// keeping the current match if it is still there.
+    pub fn refresh(&mut self, text: &str) {
+        let current = self
+            .current
+            .and_then(|index| self.matches.get(index))
+            .copied();
+
+        self.regex = None;
+        self.error = None;
+
+        if !self.is_regex || self.query.is_empty() {
+            self.matches = find(text, &self.query);
+        } else {
+            match Regex::new(&self.query) {
+                Ok(regex) => {
+                    self.matches = find_regex(text, &regex);
+                    self.regex = Some(regex);
+                }
+                Err(error) => {
+                    self.matches = Vec::new();
+                    self.error = Some(error.to_string());
+                }
+            }
+        }
+
+        self.current =
+            current.and_then(|current| self.matches.iter().position(|found| *found == current));
+    }
+
+    /// Replaces the text of a single match, returning the new text and the
+    /// length of what was put in its place.
+    pub fn replace(&self, text: &str, found: Match) -> (String, usize) {
+        let offset: usize = text
+            .split('\n')
+            .take(found.line)
+            .map(|line| line.len() + 1)
+            .sum();
+
+        let line = text[offset..].split('\n').next().unwrap_or_default();
+        let replacement = self.replacement_for(line, found);
+
+        let mut result = String::with_capacity(text.len() + replacement.len());
+        result.push_str(&text[..offset + found.start]);
+        result.push_str(&replacement);
+        result.push_str(&text[offset + found.end..]);
+
+        (result, replacement.len())
+    }
+
+    /// Replaces every match in a single pass, so a replacement that contains
+    /// the query is never replaced again.
+    pub fn replace_all(&self, text: &str) -> String {
+        let mut matches = self.matches.iter().peekable();
+        let mut result = String::with_capacity(text.len());
+
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                result.push('\n');
+            }
+
+            let mut last = 0;
+
+            while let Some(found) = matches.next_if(|found| found.line == index) {
+                result.push_str(&line[last..found.start]);
+                result.push_str(&self.replacement_for(line, *found));
+                last = found.end;
+            }
+
+            result.push_str(&line[last..]);
+        }
+
+        result
+    }
+
+    /// The first match after the cursor, wrapping around to the start of
+    /// the buffer.
+    pub fn next(&mut self, cursor: (usize, usize)) -> Option<Match> {
+        let index = self
+            .matches
+            .iter()
+            .position(|found| (found.line, found.start) >= cursor)
+            .or((!self.matches.is_empty()).then_some(0));
+
+        self.select(index)
+    }
+
+    /// The last match before the cursor, wrapping around to the end of the
+    /// buffer.
+    pub fn previous(&mut self, cursor: (usize, usize)) -> Option<Match> {
+        let index = self
+            .matches
+            .iter()
+            .rposition(|found| (found.line, found.end) < cursor)
+            .or(self.matches.len().checked_sub(1));
+
+        self.select(index)
+    }
+
+    /// A short description of the search state, like "2 of 5".
+    pub fn summary(&self) -> String {
+        if self.query.is_empty() {
+            String::new()
+        } else if self.matches.is_empty() {
+            String::from("No matches")
+        } else if let Some(current) = self.current {
+            format!("{} of {}", current + 1, self.matches.len())
+        } else {
+            format!("{} matches", self.matches.len())
+        }
+    }
+
+    /// The current match, if the cursor is still sitting at its end.
+    pub fn selected(&self, cursor: (usize, usize)) -> Option<Match> {
+        let found = self.matches.get(self.current?)?;
+
+        ((found.line, found.end) == cursor).then_some(*found)
+    }
+
+    fn select(&mut self, index: Option<usize>) -> Option<Match> {
+        self.current = index;
+        self.matches.get(index?).copied()
+    }
+
+    /// The replacement text for a match on `line`, with `$1`-style capture
+    /// groups expanded in regex mode.
+    fn replacement_for(&self, line: &str, found: Match) -> String {
+        let Some(captures) = self
+            .regex
+            .as_ref()
+            .and_then(|regex| regex.captures_at(line, found.start))
+        else {
+            return self.replacement.clone();
+        };
+
+        let mut replacement = String::new();
+        captures.expand(&self.replacement, &mut replacement);
+        replacement
+    }
+}
+
+/// Finds every non-overlapping occurrence of `query` in `text`.
+fn find(text: &str, query: &str) -> Vec<Match> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    text.split('\n')
+        .enumerate()
+        .flat_map(|(line, contents)| {
+            contents
+                .match_indices(query)
+                .map(move |(start, found)| Match {
+                    line,
+                    start,
+                    end: start + found.len(),
+                })
+        })
+        .collect()
+}
+
+/// Finds every match of `regex` in `text`, line by line.
+///
+/// Empty matches are skipped since there would be nothing to select.
+fn find_regex(text: &str, regex: &Regex) -> Vec<Match> {
+    text.split('\n')
+        .enumerate()
+        .flat_map(|(line, contents)| {
+            regex
+                .find_iter(contents)
+                .filter(|found| !found.is_empty())
+                .map(move |found| Match {
+                    line,
+                    start: found.start(),
+                    end: found.end(),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Match, Search};
+
+    fn search(query: &str, replacement: &str, text: &str) -> Search {
+        let mut search = Search {
+            query: query.to_owned(),
+            replacement: replacement.to_owned(),
+            ..Search::default()
+        };
+
+        search.refresh(text);
+        search
+    }
+
+    fn regex(query: &str, replacement: &str, text: &str) -> Search {
+        let mut search = Search {
+            query: query.to_owned(),
+            replacement: replacement.to_owned(),
+            is_regex: true,
+            ..Search::default()
+        };
+
+        search.refresh(text);
+        search
+    }
+
+    #[test]
+    fn finds_every_occurrence_on_every_line() {
+        let search = search("ab", "", "ab ab\nxab");
+
+        assert_eq!(
+            search.matches,
+            [
+                Match {
+                    line: 0,
+                    start: 0,
+                    end: 2
+                },
+                Match {
+                    line: 0,
+                    start: 3,
+                    end: 5
+                },
+                Match {
+                    line: 1,
+                    start: 1,
+                    end: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_all_without_replacing_the_replacement() {
+        let text = "cat\ncat cat";
+        let search = search("cat", "concat", text);
+
+        assert_eq!(search.replace_all(text), "concat\nconcat concat");
+    }
+
+    #[test]
+    fn replaces_a_single_match() {
+        let text = "one two\ntwo";
+        let search = search("two", "three", text);
+
+        assert_eq!(
+            search.replace(text, search.matches[1]),
+            (String::from("one two\nthree"), 5)
+        );
+    }
+
+    #[test]
+    fn finds_nothing_for_an_empty_query() {
+        let text = "some text";
+        let search = search("", "x", text);
+
+        assert!(search.matches.is_empty());
+        assert_eq!(search.replace_all(text), text);
+        assert_eq!(search.summary(), "");
+    }
+
+    #[test]
+    fn leaves_the_text_alone_without_matches() {
+        let text = "some text";
+        let mut search = search("missing", "x", text);
+
+        assert!(search.matches.is_empty());
+        assert_eq!(search.replace_all(text), text);
+        assert_eq!(search.next((0, 0)), None);
+        assert_eq!(search.summary(), "No matches");
+    }
+
+    #[test]
+    fn keeps_the_current_match_when_refreshed() {
+        let text = "a a a";
+        let mut search = search("a", "", text);
+        search.next((0, 1));
+        assert_eq!(search.current, Some(1));
+
+        search.refresh("b a a a");
+        assert_eq!(search.current, Some(0));
+
+        search.refresh("b");
+        assert_eq!(search.current, None);
+    }
+
+    #[test]
+    fn expands_groups_when_replacing_all() {
+        let text = "x = 1\ny = 22";
+        let search = regex(r"(\w+) = (\d+)", "${2} = $1", text);
+
+        assert_eq!(search.replace_all(text), "1 = x\n22 = y");
+    }
+
+    #[test]
+    fn expands_groups_when_replacing_one_match() {
+        let text = "a1 b2";
+        let search = regex(r"([a-z])(\d)", "$2$1", text);
+
+        assert_eq!(
+            search.replace(text, search.matches[1]),
+            (String::from("a1 2b"), 2)
+        );
+    }
+
+    #[test]
+    fn skips_empty_regex_matches() {
+        let search = regex("x*", "", "axb");
+
+        assert_eq!(search.matches.len(), 1);
+    }
+
+    #[test]
+    fn reports_an_invalid_regex() {
+        let text = "(a)";
+        let search = regex("(a", "b", text);
+
+        assert!(search.error.is_some());
+        assert!(search.matches.is_empty());
+        assert_eq!(search.replace_all(text), text);
+    }
+}
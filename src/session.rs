@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+const RECENT_FILES_LIMIT: usize = 10;
+
+/// State that is remembered between runs of the editor, apart from the
+/// settings kept in the configuration file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// The file that was active, out of `open_files`.
+    pub last_file: Option<PathBuf>,
+    /// Every file that was open, in the order of their tabs.
+    pub open_files: Vec<PathBuf>,
+    /// The inner size of the window when it was last resized.
+    pub window_size: Option<(u32, u32)>,
+    /// Where the window was when it was last moved, where the platform
+    /// reports it.
+    pub window_position: Option<(i32, i32)>,
+    pub recent_files: Vec<PathBuf>,
+    /// The last known `(line, column)` of the cursor in each file.
+    pub cursors: HashMap<PathBuf, (usize, usize)>,
+    /// The encoding each file was last reopened with, as displayed in the
+    /// picker.
+    pub encodings: HashMap<PathBuf, String>,
+}
+
+impl Session {
+    /// Loads the session from disk, falling back to an empty one if the file
+    /// is missing or can't be parsed.
+    pub fn load() -> Self {
+        session_file()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub async fn save(self) -> Result<(), Error> {
+        let Some(path) = session_file() else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_string_pretty(&self)
+            .map_err(|_| Error::IoError(io::ErrorKind::InvalidData))?;
+
+        if let Some(directory) = path.parent() {
+            tokio::fs::create_dir_all(directory)
+                .await
+                .map_err(|error| Error::IoError(error.kind()))?;
+        }
+
+        crate::atomic::write(&path, json.as_bytes())
+            .await
+            .map_err(|error| Error::IoError(error.kind()))
+    }
+
+    /// Moves `path` to the front of the recent files, dropping the oldest
+    /// entry once the list is full.
+    pub fn push_recent(&mut self, path: PathBuf) {
+        self.recent_files.retain(|recent| recent != &path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(RECENT_FILES_LIMIT);
+    }
+
+    pub fn remove_recent(&mut self, path: &Path) {
+        self.recent_files.retain(|recent| recent != path);
+    }
+}
+
+fn session_file() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "rio-editor").map(|dirs| dirs.config_dir().join("session.json"))
+}
@@ -0,0 +1,93 @@
+use std::{fmt, path::PathBuf};
+
+use directories::ProjectDirs;
+
+/// Boilerplate a new buffer can start from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKind {
+    Rust,
+    Html,
+    Python,
+    Markdown,
+}
+
+impl TemplateKind {
+    pub const ALL: [TemplateKind; 4] = [
+        TemplateKind::Rust,
+        TemplateKind::Html,
+        TemplateKind::Python,
+        TemplateKind::Markdown,
+    ];
+
+    /// The extension the new buffer is highlighted as.
+    pub fn extension(self) -> &'static str {
+        match self {
+            TemplateKind::Rust => "rs",
+            TemplateKind::Html => "html",
+            TemplateKind::Python => "py",
+            TemplateKind::Markdown => "md",
+        }
+    }
+
+    /// The name of the file in the templates folder that replaces the
+    /// built-in text.
+    fn file_name(self) -> &'static str {
+        match self {
+            TemplateKind::Rust => "main.rs",
+            TemplateKind::Html => "index.html",
+            TemplateKind::Python => "main.py",
+            TemplateKind::Markdown => "README.md",
+        }
+    }
+
+    fn builtin(self) -> &'static str {
+        match self {
+            TemplateKind::Rust => "fn main() {\n    \n}\n",
+            TemplateKind::Html => concat!(
+                "<!DOCTYPE html>\n",
+                "<html lang=\"en\">\n",
+                "<head>\n",
+                "    <meta charset=\"utf-8\">\n",
+                "    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n",
+                "    <title></title>\n",
+                "</head>\n",
+                "<body>\n",
+                "    \n",
+                "</body>\n",
+                "</html>\n",
+            ),
+            TemplateKind::Python => {
+                "def main():\n    pass\n\n\nif __name__ == \"__main__\":\n    main()\n"
+            }
+            TemplateKind::Markdown => "# Title\n\n",
+        }
+    }
+}
+
+impl fmt::Display for TemplateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateKind::Rust => write!(f, "Rust"),
+            TemplateKind::Html => write!(f, "HTML"),
+            TemplateKind::Python => write!(f, "Python"),
+            TemplateKind::Markdown => write!(f, "Markdown"),
+        }
+    }
+}
+
+/// The text of the template, read from the user's templates folder if it
+/// has a file for it and the built-in one otherwise.
+pub async fn load(kind: TemplateKind) -> (TemplateKind, String) {
+    let custom = match templates_folder() {
+        Some(folder) => tokio::fs::read_to_string(folder.join(kind.file_name()))
+            .await
+            .ok(),
+        None => None,
+    };
+
+    (kind, custom.unwrap_or_else(|| kind.builtin().to_owned()))
+}
+
+fn templates_folder() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "rio-editor").map(|dirs| dirs.config_dir().join("templates"))
+}